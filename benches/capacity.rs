@@ -0,0 +1,38 @@
+//! Timing of insertion into trees with a runtime node capacity and a const node capacity
+//!
+//! Run with `cargo bench --bench capacity`. This uses no benchmarking framework, so the timings
+//! are only meant for comparing the two capacity types on the same machine.
+
+mod common;
+
+use std::hint::black_box;
+
+use common::{points, time};
+use nalgebra::point;
+use quadtree::{shapes::Rect, FixedQuadTree, QuadTree, P2};
+
+const ITEMS: usize = 200_000;
+
+fn main() {
+    let items = points(ITEMS, 1, 100.0);
+    let boundary = Rect::new(point![0.0, 0.0], point![100.0, 100.0]);
+
+    let mut runtime = QuadTree::new(boundary, 8);
+    let runtime_elapsed = time(|| {
+        for item in &items {
+            black_box(runtime.insert(item));
+        }
+    });
+
+    let mut fixed = FixedQuadTree::<P2, 8>::new_fixed(boundary);
+    let fixed_elapsed = time(|| {
+        for item in &items {
+            black_box(fixed.insert(item));
+        }
+    });
+
+    assert_eq!(runtime.node_boundaries(), fixed.node_boundaries());
+    println!("insert {ITEMS} items");
+    println!("  runtime capacity: {runtime_elapsed:?}");
+    println!("  const capacity: {fixed_elapsed:?}");
+}
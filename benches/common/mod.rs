@@ -0,0 +1,24 @@
+//! Helpers shared by the benchmarks
+
+use std::time::{Duration, Instant};
+
+use nalgebra::point;
+use quadtree::P2;
+
+/// Deterministic uniformly distributed points in `[0, size)`
+pub fn points(n: usize, seed: u64, size: f64) -> Vec<P2> {
+    let mut state = seed;
+    let mut next = || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 11) as f64 / (1u64 << 53) as f64 * size
+    };
+    (0..n).map(|_| point![next(), next()]).collect()
+}
+
+pub fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
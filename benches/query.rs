@@ -0,0 +1,71 @@
+//! Timing of common queries across node capacities, along with the work each query does
+//!
+//! Run with `cargo bench`. This uses no benchmarking framework, so the timings are only meant
+//! for spotting large regressions and comparing capacities on the same machine.
+
+mod common;
+
+use std::hint::black_box;
+
+use common::{points, time};
+use nalgebra::point;
+use quadtree::{
+    shapes::{Circle, Rect},
+    QuadTree, QueryStats,
+};
+
+const ITEMS: usize = 100_000;
+const QUERIES: usize = 1_000;
+
+fn add(total: &mut QueryStats, stats: QueryStats) {
+    total.nodes_visited += stats.nodes_visited;
+    total.leaves_visited += stats.leaves_visited;
+    total.items_examined += stats.items_examined;
+}
+
+fn main() {
+    let items = points(ITEMS, 1, 1000.0);
+    let centers = points(QUERIES, 2, 1000.0);
+    let boundary = Rect::new(point![0.0, 0.0], point![1000.0, 1000.0]);
+
+    for capacity in [4, 16, 64] {
+        let mut qt = QuadTree::new(boundary, capacity);
+        let insert = time(|| {
+            black_box(qt.insert_many(&items));
+        });
+        println!("capacity {capacity}: insert_many {ITEMS} items in {insert:?}");
+
+        let rects: Vec<Rect> = centers
+            .iter()
+            .map(|c| Rect::from_center(*c, 10.0, 10.0))
+            .collect();
+        let circles: Vec<Circle> = centers.iter().map(|c| Circle::new(*c, 10.0)).collect();
+
+        let mut stats = QueryStats::default();
+        let elapsed = time(|| {
+            for rect in &rects {
+                let (found, s) = qt.query_instrumented(rect);
+                black_box(found);
+                add(&mut stats, s);
+            }
+        });
+        println!("  {QUERIES} rect queries in {elapsed:?}, {stats:?}");
+
+        let mut stats = QueryStats::default();
+        let elapsed = time(|| {
+            for circle in &circles {
+                let (found, s) = qt.query_instrumented(circle);
+                black_box(found);
+                add(&mut stats, s);
+            }
+        });
+        println!("  {QUERIES} circle queries in {elapsed:?}, {stats:?}");
+
+        let elapsed = time(|| {
+            for center in &centers {
+                black_box(qt.nearest(center));
+            }
+        });
+        println!("  {QUERIES} nearest searches in {elapsed:?}");
+    }
+}
@@ -0,0 +1,153 @@
+//! Policies for the maximum number of items a node can hold before subdividing
+
+use alloc::sync::Arc;
+
+use crate::shapes::{Rect, Shape};
+
+/// Trait for determining the maximum number of items a node can hold before subdividing
+pub trait NodeCapacity {
+    /// Get the capacity of a node from its boundary
+    fn get(&self, boundary: &Rect) -> usize;
+}
+
+/// Node capacity chosen at runtime, either fixed or computed from each node's boundary
+///
+/// This is the capacity used by [`QuadTree::new`](crate::QuadTree::new) and
+/// [`QuadTree::with_capacity_fn`](crate::QuadTree::with_capacity_fn). Nodes whose children would
+/// be smaller than a minimum size, set by a minimum cell size or a maximum depth, have unlimited
+/// capacity, so they are never subdivided.
+#[derive(Clone, Debug)]
+pub struct Capacity {
+    kind: CapacityKind,
+    min_child_width: f64,
+    min_child_height: f64,
+}
+
+#[derive(Clone)]
+enum CapacityKind {
+    Fixed(usize),
+    Fn(Arc<dyn Fn(&Rect) -> usize + Send + Sync>),
+}
+
+impl Capacity {
+    pub(crate) const fn fixed(capacity: usize) -> Self {
+        Self {
+            kind: CapacityKind::Fixed(capacity),
+            min_child_width: 0.0,
+            min_child_height: 0.0,
+        }
+    }
+
+    pub(crate) fn from_fn<F>(cap_fn: F) -> Self
+    where
+        F: Fn(&Rect) -> usize + Send + Sync + 'static,
+    {
+        Self {
+            kind: CapacityKind::Fn(Arc::new(cap_fn)),
+            min_child_width: 0.0,
+            min_child_height: 0.0,
+        }
+    }
+
+    /// Get the capacity if it is the same for every node
+    #[cfg(feature = "serde")]
+    pub(crate) const fn fixed_value(&self) -> Option<usize> {
+        match self.kind {
+            CapacityKind::Fixed(capacity) => Some(capacity),
+            CapacityKind::Fn(_) => None,
+        }
+    }
+
+    pub(crate) const fn with_min_cell_size(mut self, min_cell_size: f64) -> Self {
+        if min_cell_size > self.min_child_width {
+            self.min_child_width = min_cell_size;
+        }
+        if min_cell_size > self.min_child_height {
+            self.min_child_height = min_cell_size;
+        }
+        self
+    }
+
+    /// Prevent nodes deeper than `max_depth` below the root `boundary` from subdividing
+    pub(crate) fn with_max_depth(mut self, boundary: &Rect, max_depth: usize) -> Self {
+        // Children of a node at the maximum depth are half its size, while children of any
+        // shallower node are at least its size, so three quarters of it separates them even with
+        // rounding in the subdivided boundaries
+        let scale = (0..max_depth.min(f64::MAX_EXP as usize)).fold(0.75, |s, _| s * 0.5);
+        let size = (boundary.end() - boundary.start()) * scale;
+        self.min_child_width = self.min_child_width.max(size.x);
+        self.min_child_height = self.min_child_height.max(size.y);
+        self
+    }
+}
+
+impl NodeCapacity for Capacity {
+    fn get(&self, boundary: &Rect) -> usize {
+        let size = (boundary.end() - boundary.start()) / 2.0;
+        if size.x < self.min_child_width || size.y < self.min_child_height {
+            return usize::MAX;
+        }
+
+        match &self.kind {
+            CapacityKind::Fixed(capacity) => *capacity,
+            CapacityKind::Fn(cap_fn) => cap_fn(boundary),
+        }
+    }
+}
+
+impl core::fmt::Debug for CapacityKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Fixed(capacity) => f.debug_tuple("Fixed").field(capacity).finish(),
+            Self::Fn(_) => f.debug_tuple("Fn").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Smallest capacity chosen by [`auto_node_capacity`]
+const MIN_AUTO_CAPACITY: usize = 8;
+
+/// Largest capacity chosen by [`auto_node_capacity`]
+const MAX_AUTO_CAPACITY: usize = 64;
+
+/// Choose a node capacity for a quadtree expected to hold `expected_items` items
+///
+/// The capacity is twice the base 2 logarithm of `expected_items`, clamped to between 8 and 64,
+/// e.g. 12 for 100 items, 26 for 10,000 items, and 38 for 1,000,000 items. This is used by
+/// [`QuadTree::new_auto`](crate::QuadTree::new_auto).
+///
+/// The heuristic is based on timing [`insert_many`](crate::QuadTree::insert_many) of uniformly
+/// distributed points followed by 2,000 small circle queries and nearest neighbor searches, for
+/// 100 to 1,000,000 points and capacities from 1 to 128. Capacities below 8 were up to 4x slower
+/// on large trees because of the extra depth, while the gains from larger capacities flattened
+/// out between 32 and 64 and grew with the number of items. Clustered data may still benefit from
+/// tuning the capacity by hand.
+pub const fn auto_node_capacity(expected_items: usize) -> usize {
+    let log = match expected_items.checked_ilog2() {
+        Some(log) => log as usize,
+        None => 0,
+    };
+    let capacity = 2 * log;
+    if capacity < MIN_AUTO_CAPACITY {
+        MIN_AUTO_CAPACITY
+    } else if capacity > MAX_AUTO_CAPACITY {
+        MAX_AUTO_CAPACITY
+    } else {
+        capacity
+    }
+}
+
+/// Node capacity fixed at compile time
+///
+/// The capacity is a constant, so the comparisons against it can be folded into the insertion
+/// code and the quadtree carries no capacity field at runtime. See
+/// [`FixedQuadTree`](crate::FixedQuadTree).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConstCapacity<const CAP: usize>;
+
+impl<const CAP: usize> NodeCapacity for ConstCapacity<CAP> {
+    #[inline(always)]
+    fn get(&self, _boundary: &Rect) -> usize {
+        CAP
+    }
+}
@@ -0,0 +1,33 @@
+//! Policies for getting the position of the items stored in a quadtree
+
+use crate::{Point, P2};
+
+/// Trait for getting the 2d point position of an item stored in the quadtree
+///
+/// This is implemented for every `Fn(&T) -> P2`, so a closure can position items that don't
+/// implement [`Point`], such as types from another crate. See [`QuadTreeBy`](crate::QuadTreeBy).
+pub trait KeyFn<T> {
+    /// Get the 2d point position of an item
+    fn point(&self, item: &T) -> P2;
+}
+
+/// Key that positions items with their [`Point`] implementation
+///
+/// This is the key used by [`QuadTree::new`](crate::QuadTree::new) and every other constructor
+/// that doesn't take a key function.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PointKey;
+
+impl<T: Point> KeyFn<T> for PointKey {
+    #[inline(always)]
+    fn point(&self, item: &T) -> P2 {
+        item.point()
+    }
+}
+
+impl<T, F: Fn(&T) -> P2> KeyFn<T> for F {
+    #[inline(always)]
+    fn point(&self, item: &T) -> P2 {
+        self(item)
+    }
+}
@@ -1,9 +1,25 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod capacity;
+pub mod key;
+mod loose;
 mod quadtree;
 pub mod shapes;
+pub mod sharing;
+pub mod subdivider;
 mod util;
+pub mod visitor;
 
+pub use loose::LooseQuadTree;
 use nalgebra::Point2;
-pub use quadtree::QuadTree;
+#[cfg(feature = "serde")]
+pub use quadtree::Region;
+pub use quadtree::{
+    FixedQuadTree, InsertOutcome, QuadTree, QuadTreeBuilder, QuadTreeBy, QuadTreeError, QueryStats,
+    SharedQuadTree,
+};
 
 /// A 2-dimensional point with `f64` values (alias of [`nalgebra::Point2`])
 pub type P2 = Point2<f64>;
@@ -0,0 +1,387 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{
+    shapes::{Rect, Shape},
+    util::is_finite,
+    Point, P2,
+};
+
+/// Maximum depth of a loose quadtree, which bounds subdivision when many items share a position
+const MAX_DEPTH: usize = 32;
+
+/// A loose quadtree for indexing items that move frequently
+///
+/// Each node accepts items within its boundary scaled about its center by a `looseness` factor,
+/// so the loose bounds of neighboring nodes overlap. An item that moves only needs to be
+/// reinserted once it leaves the loose bounds of the node storing it, which makes
+/// [`LooseQuadTree::update`] cheap for small movements at the cost of queries visiting more
+/// nodes.
+///
+/// Items are stored in the deepest node whose loose bounds contain them when they are inserted,
+/// which may be an internal node.
+#[derive(Debug, Clone)]
+pub struct LooseQuadTree<T> {
+    root: LooseNode<T>,
+    node_capacity: usize,
+    looseness: f64,
+    count: usize,
+}
+
+impl<T: Point + Clone> LooseQuadTree<T> {
+    /// Create a new empty loose quadtree
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `node_capacity`: The maximum number of items a node can hold before subdividing
+    /// - `looseness`: The factor each node's boundary is scaled by to accept items
+    ///
+    /// ## Panics
+    /// If `looseness` is less than 1
+    pub fn new(boundary: Rect, node_capacity: usize, looseness: f64) -> Self {
+        assert!(looseness >= 1.0, "looseness must be at least 1");
+        Self {
+            root: LooseNode::new(boundary, looseness),
+            node_capacity,
+            looseness,
+            count: 0,
+        }
+    }
+
+    /// Insert an item into the loose quadtree
+    ///
+    /// Items with a non-finite (`NaN` or infinite) coordinate are rejected.
+    ///
+    /// **Returns** `true` if the item was inserted, or `false` if it is outside of the boundary
+    pub fn insert(&mut self, item: T) -> bool {
+        let point = item.point();
+        if !is_finite(&point) || !self.boundary().contains(&point) {
+            return false;
+        }
+
+        self.root
+            .insert(item, &point, self.node_capacity, self.looseness, 0);
+        self.count += 1;
+        true
+    }
+
+    /// Move the item at a position by replacing it with an updated item
+    ///
+    /// The item is updated in place if its new position is still within the loose bounds of the
+    /// node storing it. Otherwise, it is removed and reinserted from the root.
+    ///
+    /// **Returns** `Ok(true)` if the item was reinserted, `Ok(false)` if it was updated in place,
+    /// or an error with `new_item` if there is no item at `point` or the new position is outside
+    /// of the boundary, in which case the tree is unchanged
+    pub fn update(&mut self, point: &P2, new_item: T) -> Result<bool, T> {
+        let new_point = new_item.point();
+        if !is_finite(&new_point) || !self.boundary().contains(&new_point) {
+            return Err(new_item);
+        }
+
+        match self.root.update(point, new_item, &new_point) {
+            Ok(()) => Ok(false),
+            Err(Moved::NotFound(new_item)) => Err(new_item),
+            Err(Moved::Outside(new_item)) => {
+                self.root.remove(point);
+                self.root
+                    .insert(new_item, &new_point, self.node_capacity, self.looseness, 0);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Remove an item by its exact position
+    ///
+    /// **Returns** an `Option` containing the removed item if it exists
+    pub fn remove(&mut self, point: &P2) -> Option<T> {
+        let item = self.root.remove(point)?;
+        self.count -= 1;
+        Some(item)
+    }
+
+    /// Get an item by its exact position
+    ///
+    /// **Returns** an `Option` containing the item if it exists
+    pub fn get(&self, point: &P2) -> Option<T> {
+        self.root.get(point).cloned()
+    }
+
+    /// Query for items within a specified shape area
+    ///
+    /// **Returns** a vector of items that are found within the shape
+    pub fn query<S: Shape>(&self, shape: &S) -> Vec<T> {
+        let mut results = vec![];
+        self.root.query(shape, &mut results);
+        results
+    }
+
+    /// Get the boundary rect of the loose quadtree
+    pub fn boundary(&self) -> Rect {
+        self.root.boundary
+    }
+
+    /// Get the factor each node's boundary is scaled by to accept items
+    pub fn looseness(&self) -> f64 {
+        self.looseness
+    }
+
+    /// Get the number of items in the loose quadtree
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Get the depth of the deepest node, where the root is at depth 0
+    pub fn depth(&self) -> usize {
+        self.root.depth()
+    }
+}
+
+/// Why an item couldn't be updated in place
+enum Moved<T> {
+    NotFound(T),
+    Outside(T),
+}
+
+#[derive(Debug, Clone)]
+struct LooseNode<T> {
+    boundary: Rect,
+    loose: Rect,
+    data: Vec<T>,
+    children: Option<Box<[Self; 4]>>,
+}
+
+impl<T: Point + Clone> LooseNode<T> {
+    fn new(boundary: Rect, looseness: f64) -> Self {
+        let half_size = (boundary.end() - boundary.start()) * (looseness / 2.0);
+        let center = boundary.center();
+        Self {
+            boundary,
+            loose: Rect::new(center - half_size, center + half_size),
+            data: vec![],
+            children: None,
+        }
+    }
+
+    fn insert(&mut self, item: T, point: &P2, capacity: usize, looseness: f64, depth: usize) {
+        if let Some(children) = &mut self.children {
+            let q = quadrant(&self.boundary, point);
+            if children[q].loose.contains(point) {
+                children[q].insert(item, point, capacity, looseness, depth + 1);
+                return;
+            }
+        }
+
+        self.data.push(item);
+        if self.children.is_none() && self.data.len() > capacity && depth < MAX_DEPTH {
+            self.subdivide(capacity, looseness, depth);
+        }
+    }
+
+    fn subdivide(&mut self, capacity: usize, looseness: f64, depth: usize) {
+        let mut children = Box::new(
+            self.boundary
+                .quarter()
+                .map(|quarter| LooseNode::new(quarter, looseness)),
+        );
+
+        // Items that drifted outside of their quarter's loose bounds stay in this node
+        let mut kept = vec![];
+        for item in self.data.drain(..) {
+            let point = item.point();
+            let child = &mut children[quadrant(&self.boundary, &point)];
+            if child.loose.contains(&point) {
+                child.data.push(item);
+            } else {
+                kept.push(item);
+            }
+        }
+        for child in children.iter_mut() {
+            if child.data.len() > capacity && depth + 1 < MAX_DEPTH {
+                child.subdivide(capacity, looseness, depth + 1);
+            }
+        }
+        self.data = kept;
+        self.children = Some(children);
+    }
+
+    fn update(&mut self, point: &P2, new_item: T, new_point: &P2) -> Result<(), Moved<T>> {
+        if let Some(item) = self.data.iter_mut().find(|item| item.point() == *point) {
+            if !self.loose.contains(new_point) {
+                return Err(Moved::Outside(new_item));
+            }
+            *item = new_item;
+            return Ok(());
+        }
+
+        let mut new_item = new_item;
+        if let Some(children) = &mut self.children {
+            for child in children.iter_mut() {
+                if child.loose.contains(point) {
+                    match child.update(point, new_item, new_point) {
+                        Err(Moved::NotFound(item)) => new_item = item,
+                        result => return result,
+                    }
+                }
+            }
+        }
+        Err(Moved::NotFound(new_item))
+    }
+
+    fn remove(&mut self, point: &P2) -> Option<T> {
+        if let Some(index) = self.data.iter().position(|item| item.point() == *point) {
+            return Some(self.data.swap_remove(index));
+        }
+
+        let children = self.children.as_mut()?;
+        let item = children
+            .iter_mut()
+            .filter(|child| child.loose.contains(point))
+            .find_map(|child| child.remove(point))?;
+        if children.iter().all(LooseNode::is_empty_leaf) {
+            self.children = None;
+        }
+        Some(item)
+    }
+
+    fn get(&self, point: &P2) -> Option<&T> {
+        if let Some(item) = self.data.iter().find(|item| item.point() == *point) {
+            return Some(item);
+        }
+
+        self.children
+            .as_ref()?
+            .iter()
+            .filter(|child| child.loose.contains(point))
+            .find_map(|child| child.get(point))
+    }
+
+    fn query<S: Shape>(&self, shape: &S, results: &mut Vec<T>) {
+        if !shape.intersects_rect(&self.loose) {
+            return;
+        }
+
+        if shape.contains_rect(&self.loose) {
+            self.collect(results);
+            return;
+        }
+
+        results.extend(
+            self.data
+                .iter()
+                .filter(|item| shape.contains(&item.point()))
+                .cloned(),
+        );
+        if let Some(children) = &self.children {
+            for child in children.iter() {
+                child.query(shape, results);
+            }
+        }
+    }
+
+    fn collect(&self, results: &mut Vec<T>) {
+        results.extend(self.data.iter().cloned());
+        if let Some(children) = &self.children {
+            for child in children.iter() {
+                child.collect(results);
+            }
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match &self.children {
+            Some(children) => 1 + children.iter().map(|c| c.depth()).max().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    fn is_empty_leaf(&self) -> bool {
+        self.data.is_empty() && self.children.is_none()
+    }
+}
+
+/// Get the quadrant of a node's boundary on the side of its center that a point is on, even if
+/// the point has drifted outside of the boundary
+fn quadrant(rect: &Rect, point: &P2) -> usize {
+    let center = rect.center();
+    let col = (point.x >= center.x) as usize;
+    let row = (point.y >= center.y) as usize;
+    row * 2 + col
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::tests::{make_circle, make_rect};
+    use nalgebra::point;
+
+    use super::*;
+
+    #[test]
+    fn small_moves_stay_in_loose_cell() {
+        let mut lqt = LooseQuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1, 2.0);
+        for p in [
+            point![10.0, 10.0],
+            point![60.0, 10.0],
+            point![10.0, 60.0],
+            point![60.0, 60.0],
+            point![40.0, 40.0],
+        ] {
+            assert!(lqt.insert(p));
+        }
+        assert_eq!(lqt.count(), 5);
+        let depth = lqt.depth();
+        assert!(depth > 0, "Tree should have subdivided");
+
+        assert_eq!(
+            lqt.update(&point![40.0, 40.0], point![52.0, 45.0]),
+            Ok(false),
+            "Moving across a cell edge but within the loose bounds should not reinsert"
+        );
+        assert_eq!(lqt.depth(), depth, "Tree should not be restructured");
+        assert_eq!(lqt.get(&point![52.0, 45.0]), Some(point![52.0, 45.0]));
+        assert_eq!(lqt.get(&point![40.0, 40.0]), None);
+
+        assert_eq!(
+            lqt.update(&point![52.0, 45.0], point![95.0, 95.0]),
+            Ok(true),
+            "Moving beyond the loose bounds should reinsert"
+        );
+        assert_eq!(lqt.count(), 5);
+        assert_eq!(
+            lqt.query(&make_circle(95.0, 95.0, 1.0)),
+            vec![point![95.0, 95.0]],
+            "Reinserted item should be queryable at its new position"
+        );
+        assert!(lqt.query(&make_circle(52.0, 45.0, 1.0)).is_empty());
+    }
+
+    #[test]
+    fn query_finds_items_outside_their_strict_cells() {
+        let mut lqt = LooseQuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1, 1.5);
+        for p in [point![20.0, 20.0], point![70.0, 20.0], point![20.0, 70.0]] {
+            lqt.insert(p);
+        }
+        assert_eq!(
+            lqt.update(&point![20.0, 20.0], point![55.0, 20.0]),
+            Ok(false)
+        );
+
+        let mut found = lqt.query(&make_rect(50.0, 0.0, 100.0, 50.0));
+        found.sort_by(|a, b| a.x.total_cmp(&b.x));
+        assert_eq!(found, vec![point![55.0, 20.0], point![70.0, 20.0]]);
+        assert_eq!(lqt.query(&make_rect(0.0, 0.0, 100.0, 100.0)).len(), 3);
+
+        assert_eq!(lqt.remove(&point![55.0, 20.0]), Some(point![55.0, 20.0]));
+        assert_eq!(lqt.count(), 2);
+        assert_eq!(
+            lqt.update(&point![55.0, 20.0], point![1.0, 1.0]),
+            Err(point![1.0, 1.0]),
+            "Updating a missing item should fail"
+        );
+        assert_eq!(
+            lqt.update(&point![70.0, 20.0], point![170.0, 20.0]),
+            Err(point![170.0, 20.0]),
+            "Moving outside of the boundary should fail"
+        );
+        assert!(!lqt.insert(point![f64::NAN, 1.0]));
+    }
+}
@@ -1,21 +1,223 @@
+use alloc::{collections::BinaryHeap, format, string::String, sync::Arc, vec, vec::Vec};
+use core::{cmp::Ordering, marker::PhantomData, ops::ControlFlow};
+use nalgebra::{self as na, Vector2};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 #[cfg(feature = "serde")]
-use serde::{ser::SerializeSeq, Serialize, Serializer};
+use serde::{
+    de, ser,
+    ser::{SerializeSeq, SerializeStruct},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
 use crate::{
-    shapes::{Rect, Shape},
-    util::{determine_overlap_quadrants, determine_quadrant, group_by_quadrant},
+    capacity::{auto_node_capacity, Capacity, ConstCapacity, NodeCapacity},
+    key::{KeyFn, PointKey},
+    shapes::{Circle, Ray, Rect, Shape},
+    sharing::{Shared, Sharing, Unique},
+    subdivider::{Quarter, Subdivider},
+    util::{
+        cmp_xy, contains_any, determine_overlap_quadrants, determine_quadrant,
+        distance_squared_to_rect, expand_rect, grid_cell, group_by_quadrant, intersects_any,
+        is_finite, quantile, split_quadrant, UnionFind,
+    },
+    visitor::NodeVisitor,
     Point, P2,
 };
 
 /// A generic QuadTree implementation for spatial indexing of 2D points
+///
+/// The quadtree is `Send` and `Sync` whenever its items are, and every query takes `&self`, so a
+/// single tree can be shared between threads and queried concurrently without a lock.
+///
+/// Items are positioned with their [`Point`] implementation by default. See [`QuadTreeBy`] to
+/// position them with a key function instead.
+///
+/// Cloning the tree copies every node, so mutations modify nodes in place without copying them.
+/// See [`SharedQuadTree`] for clones that share their nodes with the original.
 #[derive(Debug)]
-pub struct QuadTree<T> {
+pub struct QuadTree<T, C = Capacity, K = PointKey, D = Quarter, M = Unique> {
     root: Node<T>,
-    node_capacity: usize,
+    capacity: C,
+    key: K,
+    subdivider: D,
     count: usize,
+    sharing: PhantomData<M>,
+}
+
+/// A builder for configuring a new [`QuadTree`], created with [`QuadTree::builder`]
+///
+/// A boundary must be set. The node capacity defaults to 8 items, and subdivision is unbounded
+/// unless a maximum depth or minimum cell size is set.
+#[derive(Debug)]
+pub struct QuadTreeBuilder<T> {
+    boundary: Option<Rect>,
+    capacity: Capacity,
+    max_depth: Option<usize>,
+    min_cell_size: f64,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Point> QuadTreeBuilder<T> {
+    fn new() -> Self {
+        Self {
+            boundary: None,
+            capacity: Capacity::fixed(8),
+            max_depth: None,
+            min_cell_size: 0.0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Set the boundary of the quadtree
+    pub fn boundary(mut self, boundary: Rect) -> Self {
+        self.boundary = Some(boundary);
+        self
+    }
+
+    /// Set the maximum number of items a node can hold before subdividing
+    pub fn node_capacity(mut self, node_capacity: usize) -> Self {
+        self.capacity = Capacity::fixed(node_capacity);
+        self
+    }
+
+    /// Compute the capacity of each node from its boundary, replacing the node capacity
+    ///
+    /// See [`QuadTree::with_capacity_fn`].
+    pub fn capacity_fn<F>(mut self, cap_fn: F) -> Self
+    where
+        F: Fn(&Rect) -> usize + Send + Sync + 'static,
+    {
+        self.capacity = Capacity::from_fn(cap_fn);
+        self
+    }
+
+    /// Set the maximum depth of the tree, where the root is at depth 0
+    ///
+    /// Nodes at the maximum depth are never subdivided and hold any number of items.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Set the minimum width and height of a subdivided cell
+    ///
+    /// See [`QuadTree::with_min_cell_size`].
+    pub fn min_cell_size(mut self, min_cell_size: f64) -> Self {
+        self.min_cell_size = min_cell_size;
+        self
+    }
+
+    /// Build the quadtree
+    ///
+    /// ## Panics
+    /// If no boundary was set
+    pub fn build(self) -> QuadTree<T> {
+        let boundary = self
+            .boundary
+            .expect("QuadTreeBuilder::build called without setting a boundary");
+        let mut capacity = self.capacity.with_min_cell_size(self.min_cell_size);
+        if let Some(max_depth) = self.max_depth {
+            capacity = capacity.with_max_depth(&boundary, max_depth);
+        }
+        QuadTree {
+            root: Node::Empty { boundary },
+            capacity,
+            key: PointKey,
+            subdivider: Quarter,
+            count: 0,
+            sharing: PhantomData,
+        }
+    }
+}
+
+/// The outcome of [`QuadTree::insert_reported`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertOutcome {
+    /// The item was inserted, causing `subdivisions` nodes to subdivide
+    Inserted { subdivisions: usize },
+    /// The item was not inserted because it is outside of the boundary
+    OutOfBounds,
+    /// The item was not inserted because it has a non-finite (`NaN` or infinite) coordinate
+    NonFinite,
+}
+
+/// Counters describing the work done by a query, see [`QuadTree::query_instrumented`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    /// The number of nodes reached by the traversal, including leaves
+    pub nodes_visited: usize,
+    /// The number of leaves reached by the traversal, including empty ones
+    pub leaves_visited: usize,
+    /// The number of stored items tested against the shape
+    pub items_examined: usize,
+}
+
+/// The items within a shape area, serialized as a sequence, see [`QuadTree::serialize_region`]
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug)]
+pub struct Region<'a, T> {
+    items: Vec<&'a T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for Region<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.items.len()))?;
+        for item in &self.items {
+            seq.serialize_element(item)?;
+        }
+        seq.end()
+    }
+}
+
+/// An error creating a [`QuadTree`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuadTreeError {
+    /// The boundary has zero or negative width or height, so it can't be subdivided
+    DegenerateBoundary,
+    /// The boundary has a non-finite (`NaN` or infinite) coordinate
+    NonFiniteBoundary,
+}
+
+impl core::fmt::Display for QuadTreeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::DegenerateBoundary => f.write_str("boundary has zero or negative area"),
+            Self::NonFiniteBoundary => f.write_str("boundary has a non-finite coordinate"),
+        }
+    }
 }
 
-impl<T: Point + Clone> QuadTree<T> {
+impl core::error::Error for QuadTreeError {}
+
+/// A quadtree that positions its items with a key function instead of the [`Point`] trait
+///
+/// The key function is stored in the quadtree and called wherever an item's position is needed,
+/// so types that can't implement [`Point`], such as types from another crate, can be indexed
+/// without a wrapper. Create one with [`QuadTree::new_with`].
+pub type QuadTreeBy<T, K> = QuadTree<T, Capacity, K>;
+
+/// A quadtree whose node capacity is fixed at compile time
+///
+/// This behaves identically to a [`QuadTree`] created with [`QuadTree::new`], but the capacity
+/// is a const generic rather than a runtime field.
+pub type FixedQuadTree<T, const CAP: usize> = QuadTree<T, ConstCapacity<CAP>>;
+
+/// A quadtree whose clones share their nodes with the original
+///
+/// Cloning one with [`QuadTree::snapshot`] takes constant time regardless of the number of items,
+/// since nodes are only copied once either tree modifies them. Create one from a [`QuadTree`] with
+/// [`QuadTree::into_shared`].
+pub type SharedQuadTree<T> = QuadTree<T, Capacity, PointKey, Quarter, Shared>;
+
+impl<T: Point> QuadTree<T> {
+    /// Create a builder for configuring a new quadtree
+    pub fn builder() -> QuadTreeBuilder<T> {
+        QuadTreeBuilder::new()
+    }
+
     /// Create a new empty quadtree
     ///
     /// ## Arguments
@@ -24,67 +226,321 @@ impl<T: Point + Clone> QuadTree<T> {
     pub const fn new(boundary: Rect, node_capacity: usize) -> Self {
         Self {
             root: Node::Empty { boundary },
-            node_capacity,
+            capacity: Capacity::fixed(node_capacity),
+            key: PointKey,
+            subdivider: Quarter,
             count: 0,
+            sharing: PhantomData,
         }
     }
 
-    /// Get current number of items stored
-    pub const fn count(&self) -> usize {
-        self.count
+    /// Create a new empty quadtree, rejecting a boundary that can't be subdivided
+    ///
+    /// [`QuadTree::new`] accepts any boundary, but one with zero area quarters into zero-size
+    /// cells, so every item ends up in leaves that never meaningfully subdivide.
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `node_capacity`: The maximum number of items a node can hold before subdividing
+    ///
+    /// **Returns** an error if the boundary has a non-finite coordinate or zero or negative width
+    /// or height
+    pub fn try_new(boundary: Rect, node_capacity: usize) -> Result<Self, QuadTreeError> {
+        if !is_finite(&boundary.start()) || !is_finite(&boundary.end()) {
+            return Err(QuadTreeError::NonFiniteBoundary);
+        }
+        if boundary.is_degenerate() {
+            return Err(QuadTreeError::DegenerateBoundary);
+        }
+        Ok(Self::new(boundary, node_capacity))
     }
 
-    /// Insert an item into the QuadTree
+    /// Create a new empty quadtree that stops subdividing cells below a minimum size
     ///
-    /// **Returns** a boolean value indicating if the item was inserted successfully
-    pub fn insert(&mut self, item: &T) -> bool {
-        let success = self.root.insert(item, self.node_capacity);
-        if success {
-            self.count += 1;
+    /// A node is not subdivided if its children's width or height would be less than
+    /// `min_cell_size`. Instead, it holds any number of items in an oversized leaf, which bounds
+    /// the depth of the tree when many points are clustered closer than a meaningful resolution.
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `node_capacity`: The maximum number of items a node can hold before subdividing
+    /// - `min_cell_size`: The minimum width and height of a subdivided cell
+    pub const fn with_min_cell_size(
+        boundary: Rect,
+        node_capacity: usize,
+        min_cell_size: f64,
+    ) -> Self {
+        Self {
+            root: Node::Empty { boundary },
+            capacity: Capacity::fixed(node_capacity).with_min_cell_size(min_cell_size),
+            key: PointKey,
+            subdivider: Quarter,
+            count: 0,
+            sharing: PhantomData,
         }
-        success
     }
 
-    /// Insert multiple items into the QuadTree
+    /// Create a new empty quadtree where the capacity of each node depends on its boundary
     ///
-    /// **Returns** a vector of items that failed to insert, if any
-    pub fn insert_many(&mut self, items: &[T]) -> Vec<T> {
-        let items = items.to_vec();
-        let num_items = items.len();
-        let mut failed = Vec::with_capacity(items.len());
-        self.root
-            .insert_many(items, self.node_capacity, &mut failed);
-        self.count += num_items - failed.len();
-        failed
+    /// This allows the subdivision to adapt to the data, e.g. by letting larger cells hold more
+    /// items. Larger leaves keep the tree shallower and reduce the number of nodes, at the cost of
+    /// scanning more items linearly when a query partially overlaps a leaf.
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `cap_fn`: Computes the maximum number of items a node can hold before subdividing from
+    ///   the node's boundary
+    pub fn with_capacity_fn<F>(boundary: Rect, cap_fn: F) -> Self
+    where
+        F: Fn(&Rect) -> usize + Send + Sync + 'static,
+    {
+        Self {
+            root: Node::Empty { boundary },
+            capacity: Capacity::from_fn(cap_fn),
+            key: PointKey,
+            subdivider: Quarter,
+            count: 0,
+            sharing: PhantomData,
+        }
     }
 
-    /// Get an item by its exact position
+    /// Create a new empty quadtree with space preallocated for an expected number of items
     ///
-    /// **Returns** an `Option` containing the item if it exists
-    pub fn get(&self, point: &P2) -> Option<T> {
-        self.root.get(point)
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `node_capacity`: The maximum number of items a node can hold before subdividing
+    /// - `expected_items`: The number of items expected to be inserted
+    pub fn with_capacity(boundary: Rect, node_capacity: usize, expected_items: usize) -> Self {
+        let mut qt = Self::new(boundary, node_capacity);
+        qt.reserve(expected_items);
+        qt
     }
 
-    /// Query for items within a specified shape area
+    /// Create a new empty quadtree with a node capacity chosen from the expected number of items
     ///
-    /// **Returns** a vector of items
-    pub fn query<S: Shape>(&self, shape: &S) -> Vec<T> {
-        let mut results = vec![];
-        self.root.query(shape, &|_| true, &mut results);
-        results
+    /// The capacity grows logarithmically with `expected_items`, as described in
+    /// [`auto_node_capacity`], and space is preallocated for the expected items.
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `expected_items`: The number of items expected to be inserted
+    pub fn new_auto(boundary: Rect, expected_items: usize) -> Self {
+        Self::with_capacity(boundary, auto_node_capacity(expected_items), expected_items)
     }
 
-    /// Query for items within a specified shape area that pass a filter
+    /// Create a new quadtree containing the given items
     ///
-    /// **Returns** a vector of items
-    pub fn query_filter<S, F>(&self, shape: &S, filter: F) -> Vec<T>
+    /// The insertion methods clone items and require `T: Clone`, since cloned trees share their
+    /// nodes until one is modified. A new tree shares nothing, so this builds one from owned
+    /// items that don't need to be `Clone`. Such a tree can still be queried by reference with
+    /// methods like [`QuadTree::query_ref`] and [`QuadTree::iter`].
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `node_capacity`: The maximum number of items a node can hold before subdividing
+    /// - `items`: The items to insert
+    ///
+    /// **Returns** the quadtree and the items that could not be inserted, such as those outside
+    /// of the boundary or with a non-finite coordinate
+    pub fn from_vec(boundary: Rect, node_capacity: usize, items: Vec<T>) -> (Self, Vec<T>) {
+        let mut qt = Self::new(boundary, node_capacity);
+        let (items, mut failed): (Vec<T>, Vec<T>) = items.into_iter().partition(|item| {
+            let point = item.point();
+            is_finite(&point) && boundary.contains(&point)
+        });
+        qt.count = items.len();
+        qt.root.insert_many(
+            items,
+            &qt.capacity,
+            &qt.subdivider,
+            &Unique::make_mut,
+            &mut failed,
+            &qt.key,
+        );
+        (qt, failed)
+    }
+}
+
+impl<T, K: Fn(&T) -> P2> QuadTreeBy<T, K> {
+    /// Create a new empty quadtree that positions items with a key function
+    ///
+    /// This allows storing items that don't implement [`Point`], such as types from another
+    /// crate, without a wrapper.
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `node_capacity`: The maximum number of items a node can hold before subdividing
+    /// - `key_fn`: Gets the position of an item, which must not change while it is stored
+    pub fn new_with(boundary: Rect, node_capacity: usize, key_fn: K) -> Self {
+        Self {
+            root: Node::Empty { boundary },
+            capacity: Capacity::fixed(node_capacity),
+            key: key_fn,
+            subdivider: Quarter,
+            count: 0,
+            sharing: PhantomData,
+        }
+    }
+}
+
+impl<T: Point, D: Subdivider> QuadTree<T, Capacity, PointKey, D> {
+    /// Create a new empty quadtree that divides nodes with a custom subdivider
+    ///
+    /// This allows splitting nodes somewhere other than their center, such as to match a known
+    /// distribution of items. See [`Subdivider`] for the contract the children must satisfy.
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `node_capacity`: The maximum number of items a node can hold before subdividing
+    /// - `subdivider`: Divides the boundary of a node into the boundaries of its children
+    pub fn new_with_subdivider(boundary: Rect, node_capacity: usize, subdivider: D) -> Self {
+        Self {
+            root: Node::Empty { boundary },
+            capacity: Capacity::fixed(node_capacity),
+            key: PointKey,
+            subdivider,
+            count: 0,
+            sharing: PhantomData,
+        }
+    }
+}
+
+impl<T: Point, const CAP: usize> FixedQuadTree<T, CAP> {
+    /// Create a new empty quadtree with a node capacity of `CAP`
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    pub const fn new_fixed(boundary: Rect) -> Self {
+        Self {
+            root: Node::Empty { boundary },
+            capacity: ConstCapacity,
+            key: PointKey,
+            subdivider: Quarter,
+            count: 0,
+            sharing: PhantomData,
+        }
+    }
+}
+
+// Methods that only access items by reference, so they don't require `T: Clone`
+impl<T, C: NodeCapacity, K: KeyFn<T>, D: Subdivider, M> QuadTree<T, C, K, D, M> {
+    /// Reserve space for at least `additional` more items
+    ///
+    /// The space is reserved in the root leaf, where it also serves as the buffer for
+    /// redistributing items when the root subdivides. This is a no-op if the root has already
+    /// been subdivided.
+    pub fn reserve(&mut self, additional: usize) {
+        match self.root {
+            Node::Empty { boundary } => {
+                self.root = Node::External {
+                    boundary,
+                    data: Vec::with_capacity(additional),
+                };
+            }
+            Node::External { ref mut data, .. } => data.reserve(additional),
+            Node::Internal { .. } => (),
+        }
+    }
+
+    /// Get current number of items stored
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    // Resets the root to a single empty node once the last item is removed, regardless of which
+    // nodes the removal visited
+    fn collapse_if_empty(&mut self) {
+        if self.count == 0 {
+            self.root = Node::Empty {
+                boundary: self.boundary(),
+            };
+        }
+    }
+
+    /// Get the boundary of the leaf cell that owns a point
+    ///
+    /// The size of the cell reflects the effective resolution of the tree at that location.
+    ///
+    /// **Returns** the boundary of the leaf that would store `point`, or `None` if the point is
+    /// outside of the quadtree's boundary
+    pub fn cell_of(&self, point: &P2) -> Option<Rect> {
+        self.root.cell_of(point)
+    }
+
+    /// Iterate over the items in order of increasing distance from a point
+    ///
+    /// Nodes are visited best-first, so only the part of the tree needed for the items actually
+    /// pulled from the iterator is explored. Items at equal distances are yielded in an
+    /// unspecified order.
+    ///
+    /// **Returns** an iterator over references to the items, nearest first
+    pub fn nearest_iter(&self, point: P2) -> impl Iterator<Item = &T> {
+        let mut heap = BinaryHeap::new();
+        heap.push(NearestEntry {
+            distance_squared: distance_squared_to_rect(&self.root.boundary(), &point),
+            kind: NearestKind::Node(&self.root),
+        });
+        NearestIter {
+            point,
+            key: &self.key,
+            heap,
+        }
+    }
+
+    /// Visit the items within a specified shape area in order of increasing distance from a
+    /// point, stopping once the callback breaks
+    ///
+    /// Like [`QuadTree::nearest_iter`], nodes are explored best-first, so nodes further away than
+    /// the last visited item are never explored after an early break. Nodes outside of the shape
+    /// are skipped entirely. Items at equal distances are visited in an unspecified order.
+    pub fn query_ordered_visit<S, F>(&self, shape: &S, origin: P2, mut f: F)
     where
         S: Shape,
-        F: Fn(&T) -> bool,
+        F: FnMut(&T) -> ControlFlow<()>,
     {
-        let mut results = vec![];
-        self.root.query(shape, &filter, &mut results);
-        results
+        let mut heap = BinaryHeap::new();
+        if shape.intersects_rect(&self.boundary()) {
+            heap.push(NearestEntry {
+                distance_squared: distance_squared_to_rect(&self.boundary(), &origin),
+                kind: NearestKind::Node(&self.root),
+            });
+        }
+
+        while let Some(entry) = heap.pop() {
+            match entry.kind {
+                NearestKind::Item(item) => {
+                    if f(item).is_break() {
+                        return;
+                    }
+                }
+                NearestKind::Node(Node::Internal { children, .. }) => {
+                    heap.extend(
+                        children
+                            .iter()
+                            .filter(|child| shape.intersects_rect(&child.boundary()))
+                            .map(|child| NearestEntry {
+                                distance_squared: distance_squared_to_rect(
+                                    &child.boundary(),
+                                    &origin,
+                                ),
+                                kind: NearestKind::Node(child),
+                            }),
+                    );
+                }
+                NearestKind::Node(Node::External { data, .. }) => {
+                    for item in data {
+                        let point = self.key.point(item);
+                        if shape.contains(&point) {
+                            heap.push(NearestEntry {
+                                distance_squared: na::distance_squared(&point, &origin),
+                                kind: NearestKind::Item(item),
+                            });
+                        }
+                    }
+                }
+                NearestKind::Node(Node::Empty { .. }) => (),
+            }
+        }
     }
 
     /// Query for items within a specified shape area
@@ -92,10 +548,26 @@ impl<T: Point + Clone> QuadTree<T> {
     /// **Returns** a vector of immutable references to items
     pub fn query_ref<S: Shape>(&self, shape: &S) -> Vec<&T> {
         let mut results = vec![];
-        self.root.query_ref(shape, &|_| true, &mut results);
+        self.root
+            .query_ref(shape, &|_| true, &mut results, &self.key);
         results
     }
 
+    /// Query for items within a viewport along with the leaf cells it overlaps, in one traversal
+    ///
+    /// The cells include empty leaves, so together they cover the part of the boundary within
+    /// the viewport.
+    ///
+    /// **Returns** references to the items within `view` and the boundary of every leaf cell that
+    /// intersects it
+    pub fn query_window(&self, view: Rect) -> (Vec<&T>, Vec<Rect>) {
+        let mut items = vec![];
+        let mut cells = vec![];
+        self.root
+            .query_window(&view, &mut items, &mut cells, &self.key);
+        (items, cells)
+    }
+
     /// Query for items within a specified shape area that pass a filter
     ///
     /// **Returns** a vector of immutable references to items
@@ -105,56 +577,78 @@ impl<T: Point + Clone> QuadTree<T> {
         F: Fn(&T) -> bool,
     {
         let mut results = vec![];
-        self.root.query_ref(shape, &filter, &mut results);
+        self.root.query_ref(shape, &filter, &mut results, &self.key);
         results
     }
 
-    /// Delete items that are within a specified shape area
+    /// Check if any item within a specified shape area passes a predicate
     ///
-    /// **Returns** the number of items that were deleted
-    pub fn delete<S: Shape>(&mut self, shape: &S) -> usize {
-        let mut deleted = 0;
-        self.root.delete(shape, &|_| true, &mut deleted);
-        self.count -= deleted;
-        deleted
-    }
-
-    /// Delete items that are within a specified shape area and pass a filter
+    /// The traversal stops as soon as a matching item is found.
     ///
-    /// **Returns** the number of items that were deleted
-    pub fn delete_filter<S, F>(&mut self, shape: &S, filter: F) -> usize
+    /// **Returns** a boolean value indicating if a matching item exists
+    pub fn any<S, F>(&self, shape: &S, predicate: F) -> bool
     where
         S: Shape,
         F: Fn(&T) -> bool,
     {
-        let mut deleted = 0;
-        self.root.delete(shape, &filter, &mut deleted);
-        self.count -= deleted;
-        deleted
+        self.root.any(shape, &predicate, &self.key)
     }
 
-    /// Pop items that are within a specified shape area
+    /// Get the centroid of the items within a specified shape area
     ///
-    /// **Returns** a vector of items that were found within the shape and removed
-    pub fn pop<S: Shape>(&mut self, shape: &S) -> Vec<T> {
-        let mut results = vec![];
-        self.root.pop(shape, &|_| true, &mut results);
-        self.count -= results.len();
-        results
+    /// The item positions are accumulated during the traversal rather than collected.
+    ///
+    /// **Returns** the average position of the matching items, or `None` if nothing matched
+    pub fn centroid<S: Shape>(&self, shape: &S) -> Option<P2> {
+        let mut sum = Vector2::zeros();
+        let mut count = 0;
+        self.root.centroid(shape, &mut sum, &mut count, &self.key);
+        (count > 0).then(|| P2::from(sum / count as f64))
     }
 
-    /// Pop items that are within a specified shape area and pass a filter
+    /// Count items within a region, bucketed into a uniform grid of `cols` by `rows` cells
     ///
-    /// **Returns** a vector of items that were found within the shape and removed
-    pub fn pop_filter<S, F>(&mut self, shape: &S, filter: F) -> Vec<T>
+    /// Points on a shared cell edge are bucketed into the cell with the greater index, and
+    /// points on the far edges of `region` are bucketed into the last column or row.
+    ///
+    /// **Returns** a row-major vector of length `cols * rows` containing the count of each cell
+    pub fn density_grid(&self, region: Rect, cols: usize, rows: usize) -> Vec<usize> {
+        self.density_grid_filter(region, cols, rows, |_| true)
+    }
+
+    /// Count items within a region that pass a filter, bucketed into a uniform grid of `cols` by
+    /// `rows` cells
+    ///
+    /// Items are bucketed as in [`QuadTree::density_grid`].
+    ///
+    /// **Returns** a row-major vector of length `cols * rows` containing the count of each cell
+    pub fn density_grid_filter<F>(
+        &self,
+        region: Rect,
+        cols: usize,
+        rows: usize,
+        filter: F,
+    ) -> Vec<usize>
     where
-        S: Shape,
         F: Fn(&T) -> bool,
     {
-        let mut results = vec![];
-        self.root.pop(shape, &filter, &mut results);
-        self.count -= results.len();
-        results
+        let mut grid = vec![0; cols * rows];
+        if !grid.is_empty() {
+            self.root
+                .density_grid(&region, cols, rows, &filter, &mut grid, &self.key);
+        }
+        grid
+    }
+
+    /// Count items within each quarter of a region in a single traversal
+    ///
+    /// The quarters are ordered as in [`Rect::quarter`]. Items outside of `region` are ignored.
+    ///
+    /// **Returns** the number of items in each quarter of `region`
+    pub fn quadrant_counts(&self, region: Rect) -> [usize; 4] {
+        let mut counts = [0; 4];
+        self.root.quadrant_counts(&region, &mut counts, &self.key);
+        counts
     }
 
     /// Return the point at the center of the boundary
@@ -166,779 +660,5903 @@ impl<T: Point + Clone> QuadTree<T> {
     pub const fn boundary(&self) -> Rect {
         self.root.boundary()
     }
-}
 
-#[cfg(feature = "serde")]
-impl<T: Serialize + Point + Clone> Serialize for QuadTree<T> {
-    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let items = self.query_ref(&self.boundary());
-        let mut seq = serializer.serialize_seq(Some(items.len()))?;
-        for item in items {
-            seq.serialize_element(item)?;
-        }
-        seq.end()
+    /// Check if a point lies exactly on an edge of the boundary
+    ///
+    /// The boundary is inclusive, so such points are inserted and queried like any other, but
+    /// they sit on the edge of the outermost cells where rounding in the caller's coordinates can
+    /// easily move them outside of the tree.
+    pub fn is_on_boundary(&self, point: &P2) -> bool {
+        let boundary = self.boundary();
+        let (start, end) = (boundary.start(), boundary.end());
+        boundary.contains(point)
+            && (point.x == start.x || point.x == end.x || point.y == start.y || point.y == end.y)
     }
-}
 
-/// QuadTree node enum
-///
-/// ## Variants
-/// - `Internal`: Contains children nodes and represents a subdivided area
-/// - `External`: Contains data and represents a leaf node
-/// - `Empty`: Represents an empty area without any data
-#[derive(Debug)]
-enum Node<T> {
-    Internal {
-        boundary: Rect,
-        children: [Box<Self>; 4],
-    },
-    External {
-        boundary: Rect,
-        data: Vec<T>,
-    },
-    Empty {
-        boundary: Rect,
-    },
-}
+    /// Clip a region to the boundary of the quadtree
+    ///
+    /// This is the part of the region that is covered by the tree, which is all that a query
+    /// with it can find. A region that only touches an edge of the boundary is clipped to a rect
+    /// with zero width or height.
+    ///
+    /// **Returns** the intersection of `region` and the boundary, or `None` if they are disjoint
+    pub fn clamp_region(&self, region: Rect) -> Option<Rect> {
+        let boundary = self.boundary();
+        boundary.intersects(&region).then(|| {
+            Rect::new(
+                region.start().sup(&boundary.start()),
+                region.end().inf(&boundary.end()),
+            )
+        })
+    }
 
-impl<T: Point + Clone> Node<T> {
-    fn insert(&mut self, item: &T, capacity: usize) -> bool {
-        let point = item.point();
+    /// Get the tight bounding box of the stored items
+    ///
+    /// Unlike [`QuadTree::boundary`], this covers only the positions of the items actually
+    /// stored, independently of how the tree is subdivided.
+    ///
+    /// **Returns** the minimal `Rect` containing every item's point, or `None` if the tree is empty
+    pub fn total_bounds(&self) -> Option<Rect> {
+        let mut points = self
+            .leaves()
+            .flat_map(|(_, items)| items)
+            .map(|item| self.key.point(item));
+        let first = points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), p| (min.inf(&p), max.sup(&p)));
+        Some(Rect::new(min, max))
+    }
 
-        if !self.boundary().contains(&point) {
-            return false;
-        }
+    /// Get a quantile of the x coordinates of the stored items
+    ///
+    /// Quantiles between two items are linearly interpolated, so `quantile_x(0.5)` of an even
+    /// number of items is the mean of the two middle coordinates.
+    ///
+    /// **Returns** the `q` quantile, or `None` if the tree is empty or `q` is not within `[0, 1]`
+    pub fn quantile_x(&self, q: f64) -> Option<f64> {
+        let mut values: Vec<f64> = self.iter().map(|item| self.key.point(item).x).collect();
+        quantile(&mut values, q)
+    }
 
-        match *self {
-            Self::Empty { boundary } => {
-                let mut data = Vec::with_capacity(capacity);
-                data.push(item.clone());
-                *self = Self::External { boundary, data };
-                true
-            }
-            Self::External {
-                boundary,
-                ref mut data,
-            } => {
-                if data.len() < capacity {
-                    data.push(item.clone());
-                    return true;
-                }
+    /// Get a quantile of the y coordinates of the stored items
+    ///
+    /// See [`QuadTree::quantile_x`].
+    ///
+    /// **Returns** the `q` quantile, or `None` if the tree is empty or `q` is not within `[0, 1]`
+    pub fn quantile_y(&self, q: f64) -> Option<f64> {
+        let mut values: Vec<f64> = self.iter().map(|item| self.key.point(item).y).collect();
+        quantile(&mut values, q)
+    }
 
-                let mut data = std::mem::take(data);
-                data.push(item.clone());
-                let children = self.subdivide();
-                *self = Self::Internal { boundary, children };
+    /// Count the leaf cells that contain at least one item
+    ///
+    /// Compared to [`QuadTree::count`], this is a cheap proxy for how dispersed the items are,
+    /// since clustered items share cells while spread out items occupy more of them.
+    ///
+    /// **Returns** the number of leaves holding data
+    pub fn occupied_cells(&self) -> usize {
+        self.leaves().count()
+    }
 
-                let mut failed = Vec::with_capacity(data.len());
-                self.insert_many(data, capacity, &mut failed);
-                failed.len() == 0
-            }
-            Self::Internal {
-                boundary,
-                ref mut children,
-            } => match determine_quadrant(&boundary, &point) {
-                Some(q) => children[q].insert(item, capacity),
-                None => false,
-            },
-        }
+    /// Get the depth of the deepest node, where the root is at depth 0
+    pub fn depth(&self) -> usize {
+        self.root.depth()
     }
 
-    fn insert_many(&mut self, mut items: Vec<T>, capacity: usize, failed: &mut Vec<T>) {
-        match *self {
-            Self::Empty { boundary } => {
-                if items.len() <= capacity {
-                    items.reserve_exact(capacity - items.len());
-                    *self = Self::External {
-                        boundary,
-                        data: items,
-                    };
-                } else {
-                    let children = self.subdivide();
-                    *self = Self::Internal { boundary, children };
-                    self.insert_many(items, capacity, failed);
-                }
-            }
-            Self::External {
-                boundary,
-                ref mut data,
-            } => {
-                if data.len() + items.len() <= capacity {
-                    data.extend(items);
-                    return;
-                }
+    /// Iterate over every item in the quadtree
+    ///
+    /// Items are yielded leaf by leaf, so items that are spatially close tend to be yielded
+    /// together.
+    ///
+    /// **Returns** an iterator over references to the items
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.leaves().flat_map(|(_, items)| items)
+    }
 
-                items.append(data);
-                let children = self.subdivide();
-                *self = Self::Internal { boundary, children };
-                self.insert_many(items, capacity, failed);
-            }
-            Self::Internal {
-                boundary,
-                ref mut children,
-            } => {
-                let mut groups = group_by_quadrant(&boundary, items).into_iter();
-                for c in children {
-                    let items = groups.next().unwrap();
-                    if items.len() > 0 {
-                        c.insert_many(items, capacity, failed)
-                    }
-                }
-                let cur_failed = groups.next().unwrap();
-                if cur_failed.len() > 0 {
-                    failed.extend(cur_failed);
-                }
-            }
-        }
+    /// Iterate over every item in the quadtree along with its position
+    ///
+    /// The position of each item is computed once, which avoids recomputing positions that are
+    /// expensive to derive.
+    ///
+    /// **Returns** an iterator over the position of each item and a reference to it
+    pub fn iter_positions(&self) -> impl Iterator<Item = (P2, &T)> {
+        self.iter().map(|item| (self.key.point(item), item))
     }
 
-    fn query<S, F>(&self, shape: &S, filter: &F, results: &mut Vec<T>)
-    where
-        S: Shape,
-        F: Fn(&T) -> bool,
-    {
-        match self {
-            Self::External { boundary, data } => {
-                if shape.contains_rect(boundary) {
-                    results.extend(data.iter().filter(|&a| filter(a)).cloned());
-                } else {
-                    for item in data {
-                        if shape.contains(&item.point()) && filter(item) {
-                            results.push(item.clone());
-                        }
-                    }
-                }
-            }
-            Self::Internal { boundary, children } => {
-                if boundary.intersects(&shape.rect()) {
-                    for q in determine_overlap_quadrants(boundary, &shape.rect()) {
-                        children[q].query(shape, filter, results);
-                    }
-                }
-            }
-            Self::Empty { .. } => (),
+    /// Iterate over the leaves of the quadtree that contain items
+    ///
+    /// Items in the same leaf are spatially close, so this is useful for processing them in
+    /// spatially coherent batches.
+    ///
+    /// **Returns** an iterator over each leaf's boundary and its items
+    pub fn leaves(&self) -> impl Iterator<Item = (Rect, &[T])> {
+        Leaves {
+            stack: vec![&self.root],
         }
     }
 
-    fn query_ref<'a, S, F>(&'a self, shape: &S, filter: &F, results: &mut Vec<&'a T>)
+    /// Create a new quadtree with the same structure by applying a function to every item
+    ///
+    /// The mapped items must have the same [`Point::point`] as the positions of the originals,
+    /// since the new tree reuses the existing structure as-is rather than subdividing again.
+    ///
+    /// **Returns** the new quadtree
+    pub fn map<U, F>(&self, f: F) -> QuadTree<U, C, PointKey, D>
     where
-        S: Shape,
-        F: Fn(&T) -> bool,
+        U: Point + Clone,
+        F: Fn(&T) -> U,
+        C: Clone,
+        D: Clone,
     {
-        match self {
-            Self::External { boundary, data } => {
-                if shape.contains_rect(boundary) {
-                    results.extend(data.iter().filter(|&a| filter(a)));
-                    return;
-                }
+        QuadTree {
+            root: self.root.map(&f),
+            capacity: self.capacity.clone(),
+            key: PointKey,
+            subdivider: self.subdivider.clone(),
+            count: self.count,
+            sharing: PhantomData,
+        }
+    }
 
-                for item in data {
-                    if shape.contains(&item.point()) && filter(item) {
-                        results.push(item);
-                    }
-                }
-            }
-            Self::Internal { boundary, children } => {
-                if boundary.intersects(&shape.rect()) {
-                    for q in determine_overlap_quadrants(boundary, &shape.rect()) {
-                        children[q].query_ref(shape, filter, results);
-                    }
-                }
-            }
-            Self::Empty { .. } => (),
+    /// Check the structural invariants of the quadtree
+    ///
+    /// - Every item in a leaf lies within the leaf's boundary
+    /// - No leaf holds more items than its capacity
+    /// - Every internal node's children quarter its boundary
+    /// - The item count matches the number of items stored in the leaves
+    ///
+    /// A leaf may hold one item even if its capacity is zero. The crate's own tests check this
+    /// after every insertion and deletion.
+    ///
+    /// **Returns** an error describing the first violation found, if any
+    pub fn validate(&self) -> Result<(), String> {
+        let mut count = 0;
+        self.root
+            .validate(&self.capacity, &self.subdivider, &mut count, &self.key)?;
+        if count != self.count {
+            return Err(format!(
+                "count is {} but {} items are stored",
+                self.count, count
+            ));
         }
+        Ok(())
     }
 
-    fn get(&self, point: &P2) -> Option<T> {
-        match self {
-            Self::External { data, .. } => {
-                for item in data {
-                    if item.point() == *point {
-                        return Some(item.clone());
-                    }
-                }
-                None
-            }
-            Self::Internal { boundary, children } => match determine_quadrant(boundary, point) {
-                Some(q) => children[q].get(point),
-                None => None,
+    /// Estimate the memory used by the quadtree in bytes
+    ///
+    /// This includes the size of every node and the allocated capacity of every leaf's item
+    /// buffer, but not any heap memory owned by the items themselves.
+    ///
+    /// **Returns** the approximate number of bytes
+    pub fn memory_usage(&self) -> usize {
+        core::mem::size_of::<Self>() + self.root.heap_size()
+    }
+
+    /// Get the boundary of every node in the quadtree, including both internal and leaf nodes
+    ///
+    /// **Returns** a vector of node boundaries paired with their depth, where the root is at depth 0
+    pub fn node_boundaries(&self) -> Vec<(Rect, usize)> {
+        let mut results = vec![];
+        self.root.node_boundaries(0, &mut results);
+        results
+    }
+
+    /// Walk the node hierarchy of the quadtree with a visitor
+    ///
+    /// Every node is visited depth-first, including empty leaves, so this exposes the full
+    /// structure of the tree for custom analysis or export. See [`NodeVisitor`].
+    pub fn walk<V: NodeVisitor<T>>(&self, visitor: &mut V) {
+        self.root.walk(0, visitor);
+    }
+}
+
+// Methods that move items in and out of the tree without cloning them, so they only require
+// `T: Clone` if the tree shares its nodes
+impl<T, C: NodeCapacity, K: KeyFn<T>, D: Subdivider, M: Sharing<T>> QuadTree<T, C, K, D, M> {
+    /// Release spare capacity held by the item buffers of every leaf
+    ///
+    /// Leaves keep their capacity as items are removed, so this can reclaim a significant amount
+    /// of memory in long-lived trees after heavy deletion. It is the counterpart to
+    /// [`QuadTree::reserve`]. Nodes still shared with a clone of the tree are skipped, since
+    /// shrinking them would mean copying them first.
+    pub fn shrink_to_fit(&mut self) {
+        self.root.shrink_to_fit();
+    }
+
+    /// Change the boundary of the quadtree and reinsert every item
+    ///
+    /// **Returns** a vector of items that no longer fit within `new_boundary`, which are removed
+    pub fn resize(&mut self, new_boundary: Rect) -> Vec<T> {
+        let root = core::mem::replace(
+            &mut self.root,
+            Node::Empty {
+                boundary: new_boundary,
             },
-            Self::Empty { .. } => None,
+        );
+        let mut items = Vec::with_capacity(self.count);
+        root.into_items(&M::make_mut, &mut items);
+        self.count = 0;
+        self.insert_vec(items)
+    }
+
+    /// Insert an owned item into the QuadTree, moving it into place without cloning
+    ///
+    /// Items with a non-finite (`NaN` or infinite) coordinate are rejected.
+    ///
+    /// **Returns** the item back as an error if it could not be inserted
+    pub fn insert_owned(&mut self, item: T) -> Result<(), T> {
+        if !is_finite(&self.key.point(&item)) {
+            return Err(item);
         }
+
+        self.root.insert(
+            item,
+            &self.capacity,
+            &self.subdivider,
+            &M::make_mut,
+            &mut |_| (),
+            &self.key,
+        )?;
+        self.count += 1;
+        #[cfg(all(debug_assertions, test))]
+        assert_eq!(self.validate(), Ok(()));
+        Ok(())
     }
 
-    // Returns true if the node is empty after deletion
-    fn delete<S, F>(&mut self, shape: &S, filter: &F, deleted: &mut usize) -> bool
-    where
-        S: Shape,
-        F: Fn(&T) -> bool,
-    {
-        match *self {
-            Self::External {
-                boundary,
-                ref mut data,
-            } => {
-                if !boundary.intersects(&shape.rect()) {
-                    return false;
-                }
+    // Returns the items that failed to insert
+    fn insert_vec(&mut self, items: Vec<T>) -> Vec<T> {
+        let num_items = items.len();
+        let boundary = self.boundary();
+        let (items, mut failed): (Vec<T>, Vec<T>) = items.into_iter().partition(|item| {
+            let point = self.key.point(item);
+            is_finite(&point) && boundary.contains(&point)
+        });
+        failed.reserve(items.len());
+        self.root.insert_many(
+            items,
+            &self.capacity,
+            &self.subdivider,
+            &M::make_mut,
+            &mut failed,
+            &self.key,
+        );
+        self.count += num_items - failed.len();
+        failed
+    }
 
-                let original_data_len = data.len();
-                data.retain(|item| !(shape.contains(&item.point()) && filter(item)));
-                *deleted += original_data_len - data.len();
+    /// Remove the item nearest to a point
+    ///
+    /// Calling this repeatedly drains the tree in order of increasing distance from `point`.
+    ///
+    /// **Returns** an `Option` containing the removed item, or `None` if the tree is empty
+    pub fn pop_nearest(&mut self, point: &P2) -> Option<T> {
+        let mut best = None;
+        self.root
+            .nearest(point, &|_| true, 1.0, &mut best, &self.key);
+        let target = self.key.point(best?.0);
+        let (item, _) = self
+            .root
+            .remove(&target, &|_| true, &M::make_mut, &self.key)?;
+        self.count -= 1;
+        self.collapse_if_empty();
+        Some(item)
+    }
 
-                if data.is_empty() {
-                    *self = Self::Empty { boundary };
-                    return true;
-                }
+    /// Remove an item by its exact position
+    ///
+    /// If several items share the position, only one of them is removed.
+    ///
+    /// **Returns** an `Option` containing the removed item if it exists
+    pub fn remove(&mut self, point: &P2) -> Option<T> {
+        let (item, _) = self
+            .root
+            .remove(point, &|_| true, &M::make_mut, &self.key)?;
+        self.count -= 1;
+        self.collapse_if_empty();
+        Some(item)
+    }
 
-                false
-            }
-            Self::Internal {
-                boundary,
-                ref mut children,
-            } => {
-                if boundary.intersects(&shape.rect()) {
-                    let mut is_all_empty = true;
-                    for c in children {
-                        let is_empty = c.delete(shape, filter, deleted);
-                        if !is_empty {
-                            is_all_empty = false;
-                        }
-                    }
-                    if is_all_empty {
-                        *self = Self::Empty { boundary };
-                        return true;
-                    }
-                }
+    /// Remove a specific item, matching by equality rather than only by position
+    ///
+    /// Unlike [`QuadTree::remove`], this distinguishes between different items stored at the
+    /// same position. If several stored items are equal to `item`, only one of them is removed.
+    ///
+    /// **Returns** `true` if an equal item was found and removed
+    pub fn remove_item(&mut self, item: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        let point = self.key.point(item);
+        if self
+            .root
+            .remove(&point, &|stored| stored == item, &M::make_mut, &self.key)
+            .is_none()
+        {
+            return false;
+        }
+        self.count -= 1;
+        self.collapse_if_empty();
+        true
+    }
 
-                false
-            }
-            Self::Empty { .. } => true,
+    /// Replace the item at an exact position with a new item at the same position
+    ///
+    /// **Returns** an `Option` containing the replaced item, or `None` without making any change
+    /// if no item exists at `point` or `new_item` is not positioned at `point`
+    pub fn replace(&mut self, point: &P2, new_item: T) -> Option<T> {
+        if self.key.point(&new_item) != *point {
+            return None;
         }
+        self.root.replace(point, new_item, &M::make_mut, &self.key)
     }
 
-    // Returns true if the node is empty after deletion
-    fn pop<S, F>(&mut self, shape: &S, filter: &F, results: &mut Vec<T>) -> bool
+    /// Delete items that are within a specified shape area
+    ///
+    /// **Returns** the number of items that were deleted
+    pub fn delete<S: Shape>(&mut self, shape: &S) -> usize {
+        let mut deleted = 0;
+        self.root
+            .delete(shape, &|_| true, &mut deleted, &M::make_mut, &self.key);
+        self.count -= deleted;
+        self.collapse_if_empty();
+        #[cfg(all(debug_assertions, test))]
+        assert_eq!(self.validate(), Ok(()));
+        deleted
+    }
+
+    /// Delete items that are within a specified shape area and pass a filter
+    ///
+    /// **Returns** the number of items that were deleted
+    pub fn delete_filter<S, F>(&mut self, shape: &S, filter: F) -> usize
     where
         S: Shape,
         F: Fn(&T) -> bool,
     {
-        match *self {
-            Self::External {
-                boundary,
-                ref mut data,
-            } => {
-                if !boundary.intersects(&shape.rect()) {
-                    return false;
-                }
+        let mut deleted = 0;
+        self.root
+            .delete(shape, &filter, &mut deleted, &M::make_mut, &self.key);
+        self.count -= deleted;
+        self.collapse_if_empty();
+        deleted
+    }
 
-                let mut left_data = Vec::with_capacity(data.capacity());
-                for item in data.drain(..) {
-                    if shape.contains(&item.point()) && filter(&item) {
-                        results.push(item);
-                    } else {
-                        left_data.push(item);
+    /// Delete items that are within any of the specified shape areas in a single traversal
+    ///
+    /// **Returns** the number of items that were deleted
+    pub fn delete_many<S: Shape>(&mut self, shapes: &[S]) -> usize {
+        let mut deleted = 0;
+        self.root
+            .delete_many(shapes, &mut deleted, &M::make_mut, &self.key);
+        self.count -= deleted;
+        self.collapse_if_empty();
+        deleted
+    }
+
+    /// Remove and return items that are within a specified shape area
+    ///
+    /// This is the canonical name for this operation, and [`QuadTree::pop`] is kept as a synonym.
+    /// Use [`QuadTree::delete`] instead if the removed items aren't needed.
+    ///
+    /// **Returns** a vector of items that were found within the shape and removed
+    pub fn take<S: Shape>(&mut self, shape: &S) -> Vec<T> {
+        let mut results = vec![];
+        self.root.pop(
+            shape,
+            &|_| true,
+            &mut |item| results.push(item),
+            &M::make_mut,
+            &self.key,
+        );
+        self.count -= results.len();
+        self.collapse_if_empty();
+        results
+    }
+
+    /// Remove and return items that are within a specified shape area and pass a filter
+    ///
+    /// This is the canonical name for this operation, and [`QuadTree::pop_filter`] is kept as a
+    /// synonym. Use [`QuadTree::delete_filter`] instead if the removed items aren't needed.
+    ///
+    /// **Returns** a vector of items that were found within the shape and removed
+    pub fn take_filter<S, F>(&mut self, shape: &S, filter: F) -> Vec<T>
+    where
+        S: Shape,
+        F: Fn(&T) -> bool,
+    {
+        let mut results = vec![];
+        self.root.pop(
+            shape,
+            &filter,
+            &mut |item| results.push(item),
+            &M::make_mut,
+            &self.key,
+        );
+        self.count -= results.len();
+        self.collapse_if_empty();
+        results
+    }
+
+    /// Pop items that are within a specified shape area
+    ///
+    /// This is a synonym of [`QuadTree::take`], which is the canonical name.
+    ///
+    /// **Returns** a vector of items that were found within the shape and removed
+    pub fn pop<S: Shape>(&mut self, shape: &S) -> Vec<T> {
+        self.take(shape)
+    }
+
+    /// Pop items that are within a specified shape area and pass a filter
+    ///
+    /// This is a synonym of [`QuadTree::take_filter`], which is the canonical name.
+    ///
+    /// **Returns** a vector of items that were found within the shape and removed
+    pub fn pop_filter<S, F>(&mut self, shape: &S, filter: F) -> Vec<T>
+    where
+        S: Shape,
+        F: Fn(&T) -> bool,
+    {
+        self.take_filter(shape, filter)
+    }
+
+    /// Pop items that are within a specified shape area, passing each to a sink as it is removed
+    ///
+    /// This removes the same items as [`QuadTree::pop`] without collecting them into a vector,
+    /// which is useful for forwarding them elsewhere, such as into a channel.
+    ///
+    /// **Returns** the number of items removed
+    pub fn pop_into<S: Shape, F: FnMut(T)>(&mut self, shape: &S, mut sink: F) -> usize {
+        let mut popped = 0;
+        let mut counted_sink = |item| {
+            popped += 1;
+            sink(item);
+        };
+        self.root
+            .pop(shape, &|_| true, &mut counted_sink, &M::make_mut, &self.key);
+        self.count -= popped;
+        self.collapse_if_empty();
+        popped
+    }
+
+    /// Remove and return every item that passes a filter, wherever it is in the tree
+    ///
+    /// This is the same as [`QuadTree::pop_filter`] with the boundary as the shape, and leaves
+    /// that are emptied are collapsed.
+    ///
+    /// **Returns** a vector of the removed items
+    pub fn drain_filter<F: Fn(&T) -> bool>(&mut self, predicate: F) -> Vec<T> {
+        self.pop_filter(&self.boundary(), predicate)
+    }
+
+    /// Modify the items within a specified shape area, allowing their positions to change
+    ///
+    /// Matching items are removed, passed to `f`, and then reinserted at their new positions,
+    /// so the tree stays valid even if `f` moves them. This is the way to reposition a
+    /// selection of items in bulk.
+    ///
+    /// **Returns** a vector of items that were moved outside of the boundary or to a non-finite
+    /// position, which are no longer in the tree
+    pub fn update_matching<S, F>(&mut self, shape: &S, mut f: F) -> Vec<T>
+    where
+        S: Shape,
+        F: FnMut(&mut T),
+    {
+        let mut items = self.pop(shape);
+        items.iter_mut().for_each(&mut f);
+        self.insert_vec(items)
+    }
+}
+
+// Methods that clone items
+impl<T: Clone, C: NodeCapacity, K: KeyFn<T>, D: Subdivider, M: Sharing<T>> QuadTree<T, C, K, D, M> {
+    /// Insert an item into the QuadTree
+    ///
+    /// Items with a non-finite (`NaN` or infinite) coordinate are rejected.
+    ///
+    /// **Returns** a boolean value indicating if the item was inserted successfully
+    pub fn insert(&mut self, item: &T) -> bool {
+        self.insert_owned(item.clone()).is_ok()
+    }
+
+    /// Insert an item into the QuadTree, reporting how the tree changed
+    ///
+    /// This behaves like [`QuadTree::insert`], but reports the number of nodes that were
+    /// subdivided to make room for the item, which is useful for detecting insertion orders that
+    /// cause excessive restructuring.
+    ///
+    /// **Returns** the outcome of the insertion
+    pub fn insert_reported(&mut self, item: &T) -> InsertOutcome {
+        if !is_finite(&self.key.point(item)) {
+            return InsertOutcome::NonFinite;
+        }
+
+        match self.root.insert(
+            item.clone(),
+            &self.capacity,
+            &self.subdivider,
+            &M::make_mut,
+            &mut |_| (),
+            &self.key,
+        ) {
+            Ok(subdivisions) => {
+                self.count += 1;
+                InsertOutcome::Inserted { subdivisions }
+            }
+            Err(_) => InsertOutcome::OutOfBounds,
+        }
+    }
+
+    /// Insert an item into the QuadTree, notifying of items moved to make room for it
+    ///
+    /// This behaves like [`QuadTree::insert`], but when the insertion subdivides a full leaf,
+    /// `on_change` is called with each item that was stored in that leaf before they are moved
+    /// into its new children. The inserted item itself is not reported. This allows keeping an
+    /// external cache keyed by cell in sync without rebuilding it.
+    ///
+    /// **Returns** a boolean value indicating if the item was inserted successfully
+    pub fn insert_notify<F: FnMut(&T)>(&mut self, item: &T, mut on_change: F) -> bool {
+        if !is_finite(&self.key.point(item)) {
+            return false;
+        }
+
+        let inserted = self
+            .root
+            .insert(
+                item.clone(),
+                &self.capacity,
+                &self.subdivider,
+                &M::make_mut,
+                &mut on_change,
+                &self.key,
+            )
+            .is_ok();
+        if inserted {
+            self.count += 1;
+        }
+        inserted
+    }
+
+    /// Insert multiple items into the QuadTree
+    ///
+    /// Items with a non-finite (`NaN` or infinite) coordinate are rejected.
+    ///
+    /// **Returns** a vector of items that failed to insert, if any
+    pub fn insert_many(&mut self, items: &[T]) -> Vec<T> {
+        self.insert_vec(items.to_vec())
+    }
+
+    /// Insert every item of a slice into the QuadTree, cloning them as
+    /// [`QuadTree::insert_many`] does
+    ///
+    /// **Returns** a vector of items that failed to insert, if any
+    pub fn extend_from_slice(&mut self, items: &[T]) -> Vec<T> {
+        self.insert_many(items)
+    }
+
+    /// Insert multiple items into the QuadTree, reporting which items failed
+    ///
+    /// This behaves like [`QuadTree::insert_many`], but keeps the position of each failed item in
+    /// `items` so failures can be traced back to the source data.
+    ///
+    /// **Returns** a vector of the index and value of each item that failed to insert, such as
+    /// those outside of the boundary or with a non-finite coordinate, in their original order
+    pub fn insert_many_reported(&mut self, items: &[T]) -> Vec<(usize, T)> {
+        let boundary = self.boundary();
+        let mut accepted = Vec::with_capacity(items.len());
+        let mut rejected = vec![];
+        for (i, item) in items.iter().enumerate() {
+            let point = self.key.point(item);
+            if is_finite(&point) && boundary.contains(&point) {
+                accepted.push(item.clone());
+            } else {
+                rejected.push((i, item.clone()));
+            }
+        }
+
+        // Every accepted item lies within the root's boundary, so none can fail
+        let num_accepted = accepted.len();
+        let mut failed = Vec::new();
+        self.root.insert_many(
+            accepted,
+            &self.capacity,
+            &self.subdivider,
+            &M::make_mut,
+            &mut failed,
+            &self.key,
+        );
+        debug_assert!(failed.is_empty());
+        self.count += num_accepted;
+        rejected
+    }
+
+    /// Get an item by its exact position
+    ///
+    /// **Returns** an `Option` containing the item if it exists
+    pub fn get(&self, point: &P2) -> Option<T> {
+        self.root.get(point, &self.key)
+    }
+
+    /// Get an item by its position, allowing for a small difference in the coordinates
+    ///
+    /// This is useful when `point` was computed differently from the stored position, so they
+    /// may not be exactly equal. Every cell within `tolerance` of `point` is searched, so an item
+    /// across a cell border is still found.
+    ///
+    /// **Returns** an `Option` containing the first item found within `tolerance` distance of
+    /// `point`, which is not necessarily the closest one, or `None` if `tolerance` is negative or
+    /// not finite
+    pub fn get_approx(&self, point: &P2, tolerance: f64) -> Option<T> {
+        let area = Circle::try_new(*point, tolerance)?;
+        self.root.find(&area, &self.key).cloned()
+    }
+
+    /// Get the item stored at the position of `item`, inserting `item` if there is none
+    ///
+    /// If there is no existing item and `item` can't be inserted, such as when it lies outside of
+    /// the boundary, it is returned without being stored.
+    ///
+    /// **Returns** the existing item, or a clone of the inserted item
+    pub fn get_or_insert(&mut self, item: &T) -> T {
+        if let Some(existing) = self.get(&self.key.point(item)) {
+            return existing;
+        }
+
+        self.insert(item);
+        item.clone()
+    }
+
+    /// Get items by their exact positions
+    ///
+    /// The positions are grouped by quadrant at each level, so each subtree is descended once
+    /// for all of the positions within it.
+    ///
+    /// **Returns** a vector with the item at each position, or `None` where no item exists, in the
+    /// same order as `points`
+    pub fn get_many(&self, points: &[P2]) -> Vec<Option<T>> {
+        let mut results = vec![None; points.len()];
+        self.root.get_many(
+            points.iter().copied().enumerate().collect(),
+            &mut results,
+            &self.key,
+        );
+        results
+    }
+
+    /// Get the item nearest to a point
+    ///
+    /// **Returns** an `Option` containing the nearest item, or `None` if the tree is empty
+    pub fn nearest(&self, point: &P2) -> Option<T> {
+        let mut best = None;
+        self.root
+            .nearest(point, &|_| true, 1.0, &mut best, &self.key);
+        best.map(|(item, _)| item.clone())
+    }
+
+    /// Get an item approximately nearest to a point
+    ///
+    /// The returned item is at most `1 + eps` times farther from `point` than the true nearest
+    /// item. Nodes are pruned once they can't improve on the best distance found so far by more
+    /// than that factor, so larger values of `eps` visit fewer nodes and return faster at the
+    /// cost of accuracy. With `eps = 0`, this is the same as [`QuadTree::nearest`].
+    ///
+    /// **Returns** an `Option` containing an approximately nearest item, or `None` if the tree is
+    /// empty
+    pub fn nearest_approx(&self, point: &P2, eps: f64) -> Option<T> {
+        let factor = 1.0 + eps.max(0.0);
+        let shrink = 1.0 / (factor * factor);
+        let mut best = None;
+        self.root
+            .nearest(point, &|_| true, shrink, &mut best, &self.key);
+        best.map(|(item, _)| item.clone())
+    }
+
+    /// Get the item nearest to a point, skipping items at any of the excluded positions
+    ///
+    /// Each candidate is checked against `exclude` with a linear scan, which is cheap for the
+    /// small sets typical of iterative matching. For large exclusion sets, prefer removing the
+    /// consumed items from the tree.
+    ///
+    /// **Returns** an `Option` containing the nearest item that isn't excluded, or `None` if there
+    /// is no such item
+    pub fn nearest_excluding(&self, point: &P2, exclude: &[P2]) -> Option<T> {
+        let mut best = None;
+        self.root.nearest(
+            point,
+            &|item| !exclude.contains(&self.key.point(item)),
+            1.0,
+            &mut best,
+            &self.key,
+        );
+        best.map(|(item, _)| item.clone())
+    }
+
+    /// Find the closest pair of items in the tree
+    ///
+    /// Pairs within each leaf are compared first to establish a bound, after which each item
+    /// only searches the cells within that distance for a closer partner across cell borders.
+    ///
+    /// **Returns** the two closest items and the distance between them, or `None` if there are
+    /// fewer than two items
+    pub fn closest_pair(&self) -> Option<(T, T, f64)> {
+        let mut best = None;
+        for (_, items) in self.leaves() {
+            for (i, a) in items.iter().enumerate() {
+                for b in &items[i + 1..] {
+                    let distance = na::distance_squared(&self.key.point(a), &self.key.point(b));
+                    if best.is_none_or(|(_, _, d)| distance < d) {
+                        best = Some((a, b, distance));
                     }
                 }
+            }
+        }
 
-                if left_data.is_empty() {
-                    *self = Self::Empty { boundary };
-                    true
-                } else {
-                    *data = left_data;
-                    false
-                }
+        for (_, items) in self.leaves() {
+            for item in items {
+                self.root.closest_to(item, &mut best, &self.key);
             }
-            Self::Internal {
-                boundary,
-                ref mut children,
-            } => {
-                if boundary.intersects(&shape.rect()) {
-                    let mut is_all_empty = true;
-                    for c in children {
-                        let is_empty = c.pop(shape, filter, results);
-                        if !is_empty {
-                            is_all_empty = false;
-                        }
-                    }
-                    if is_all_empty {
-                        *self = Self::Empty { boundary };
-                        return true;
+        }
+
+        best.map(|(a, b, _)| {
+            (
+                a.clone(),
+                b.clone(),
+                na::distance(&self.key.point(a), &self.key.point(b)),
+            )
+        })
+    }
+
+    /// Group the items into clusters of points connected within a radius
+    ///
+    /// Two items are linked if they are at most `radius` apart, and a cluster is every item
+    /// connected through a chain of such links, so its members may be much further apart than
+    /// `radius`. Neighbors are found with a circular query around each item. A negative or NaN
+    /// `radius` links nothing, so every item is in a cluster of its own.
+    ///
+    /// **Returns** the clusters in the order of their first item in [`QuadTree::iter`], each
+    /// with its items in that same order
+    pub fn clusters(&self, radius: f64) -> Vec<Vec<T>> {
+        let items: Vec<&T> = self.iter().collect();
+        let mut sets = UnionFind::new(items.len());
+
+        if radius >= 0.0 {
+            // Items at the same position are always linked, so a neighbor is identified by the
+            // index of any item at its position
+            let cmp_points = |a: &P2, b: &P2| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y));
+            let mut positions: Vec<(P2, usize)> = items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| (self.key.point(item), i))
+                .collect();
+            positions.sort_unstable_by(|(a, _), (b, _)| cmp_points(a, b));
+
+            for (point, i) in &positions {
+                let area = Circle::new(*point, radius);
+                for neighbor in self.query_ref(&area) {
+                    let neighbor = self.key.point(neighbor);
+                    if let Ok(j) = positions.binary_search_by(|(p, _)| cmp_points(p, &neighbor)) {
+                        sets.union(*i, positions[j].1);
                     }
                 }
+            }
+        }
 
-                false
+        let mut clusters: Vec<Vec<T>> = vec![];
+        let mut cluster_of_root = vec![usize::MAX; items.len()];
+        for (i, item) in items.iter().enumerate() {
+            let root = sets.find(i);
+            if cluster_of_root[root] == usize::MAX {
+                cluster_of_root[root] = clusters.len();
+                clusters.push(vec![]);
             }
-            Self::Empty { .. } => true,
+            clusters[cluster_of_root[root]].push((*item).clone());
         }
+        clusters
     }
 
-    fn center(&self) -> P2 {
-        self.boundary().center()
+    /// Cast a ray and get the first item within `tolerance` of it
+    ///
+    /// Nodes are visited front to back in order of where the ray enters them, so the search stops
+    /// once no unvisited node could hold a closer hit. Items behind the origin are ignored.
+    ///
+    /// ## Arguments
+    /// - `origin`: The origin of the ray
+    /// - `direction`: The direction of the ray, which doesn't need to be normalized
+    /// - `tolerance`: The maximum perpendicular distance from the ray for an item to be hit
+    ///
+    /// **Returns** the hit item nearest to the origin along the ray, or `None` if nothing is hit
+    /// or the direction is zero
+    pub fn raycast(&self, origin: P2, direction: Vector2<f64>, tolerance: f64) -> Option<T> {
+        let ray = Ray::try_new(origin, direction)?;
+        let mut best = None;
+        self.root.raycast(&ray, tolerance, &mut best, &self.key);
+        best.map(|(item, _)| item.clone())
     }
 
-    const fn boundary(&self) -> Rect {
-        match self {
-            Self::Empty { boundary } => *boundary,
-            Self::External { boundary, .. } => *boundary,
-            Self::Internal { boundary, .. } => *boundary,
-        }
+    /// Query for items within a specified shape area
+    ///
+    /// **Returns** a vector of items
+    pub fn query<S: Shape>(&self, shape: &S) -> Vec<T> {
+        let mut results = vec![];
+        self.root.query(
+            shape,
+            &|_| true,
+            &mut results,
+            &mut QueryStats::default(),
+            &self.key,
+        );
+        results
+    }
+
+    /// Query for items within a specified shape area, counting the work done by the traversal
+    ///
+    /// This returns the same items as [`QuadTree::query`], along with how many nodes, leaves, and
+    /// items were examined to find them, which shows how effectively the tree prunes a query.
+    /// This is useful for comparing node capacities or shapes.
+    ///
+    /// **Returns** a vector of items and the statistics of the query
+    pub fn query_instrumented<S: Shape>(&self, shape: &S) -> (Vec<T>, QueryStats) {
+        let mut results = vec![];
+        let mut stats = QueryStats::default();
+        self.root
+            .query(shape, &|_| true, &mut results, &mut stats, &self.key);
+        (results, stats)
+    }
+
+    /// Query for items within a rect given as anything that converts into one, such as a
+    /// `(start, end)` tuple of points or a `[start.x, start.y, end.x, end.y]` array
+    ///
+    /// **Returns** a vector of items
+    pub fn query_rect<R: Into<Rect>>(&self, region: R) -> Vec<T> {
+        self.query(&region.into())
+    }
+
+    /// Query for items within a specified shape area along with their distance to its edge
+    ///
+    /// See [`Shape::distance_to`]. Since the items are within the shape, the distances are zero
+    /// or negative. Custom shapes that don't override it get the distance to their bounding rect.
+    ///
+    /// **Returns** a vector of items paired with their signed distance to the edge of the shape
+    pub fn query_with_distance<S: Shape>(&self, shape: &S) -> Vec<(T, f64)> {
+        self.query(shape)
+            .into_iter()
+            .map(|item| {
+                let distance = shape.distance_to(&self.key.point(&item));
+                (item, distance)
+            })
+            .collect()
+    }
+
+    /// Query for items within a specified shape area along with the depth of their leaf
+    ///
+    /// The root is at depth 0, so items in densely populated regions, where the tree is
+    /// subdivided further, have greater depths.
+    ///
+    /// **Returns** a vector of items paired with the depth of the leaf storing them
+    pub fn query_with_depth<S: Shape>(&self, shape: &S) -> Vec<(T, usize)> {
+        let mut results = vec![];
+        self.root
+            .query_with_depth(shape, 0, &mut results, &self.key);
+        results
+    }
+
+    /// Query for items within a shape chosen at runtime
+    ///
+    /// **Returns** a vector of items
+    pub fn query_dyn(&self, shape: &dyn Shape) -> Vec<T> {
+        let mut results = vec![];
+        self.root.query(
+            shape,
+            &|_| true,
+            &mut results,
+            &mut QueryStats::default(),
+            &self.key,
+        );
+        results
+    }
+
+    /// Query for items within a specified shape area that pass a filter
+    ///
+    /// **Returns** a vector of items
+    pub fn query_filter<S, F>(&self, shape: &S, filter: F) -> Vec<T>
+    where
+        S: Shape,
+        F: Fn(&T) -> bool,
+    {
+        let mut results = vec![];
+        self.root.query(
+            shape,
+            &filter,
+            &mut results,
+            &mut QueryStats::default(),
+            &self.key,
+        );
+        results
+    }
+
+    /// Query for items within a specified shape area, sorted by x and then by y
+    ///
+    /// The order of [`QuadTree::query`] results depends on the structure of the tree, while this
+    /// order only depends on the positions of the items. Items at the same position keep their
+    /// relative order from the tree traversal. Stored positions are never `NaN`, but coordinates
+    /// that don't compare fall back to [`f64::total_cmp`].
+    ///
+    /// **Returns** a sorted vector of items
+    pub fn query_sorted_xy<S: Shape>(&self, shape: &S) -> Vec<T> {
+        let mut results = self.query(shape);
+        results.sort_by(|a, b| cmp_xy(&self.key.point(a), &self.key.point(b)));
+        results
     }
 
-    fn subdivide(&self) -> [Box<Self>; 4] {
-        let rects = self.boundary().quarter();
-        rects.map(|r| Box::new(Self::Empty { boundary: r }))
+    /// Query for items that are not within a specified shape area
+    ///
+    /// Subtrees entirely within the shape are skipped, but every other part of the tree is
+    /// visited.
+    ///
+    /// **Returns** a vector of items outside of the shape
+    pub fn query_outside<S: Shape>(&self, shape: &S) -> Vec<T> {
+        let mut results = vec![];
+        self.root.query_outside(shape, &mut results, &self.key);
+        results
+    }
+
+    /// Query for at most `limit` items within a specified shape area
+    ///
+    /// The traversal stops as soon as `limit` items have been found, so which items are returned
+    /// is unspecified when more than `limit` items match.
+    ///
+    /// **Returns** a vector of at most `limit` items
+    pub fn query_limit<S: Shape>(&self, shape: &S, limit: usize) -> Vec<T> {
+        let mut results = vec![];
+        if limit > 0 {
+            self.root.query_limit(shape, limit, &mut results, &self.key);
+        }
+        results
+    }
+
+    /// Query for items within a specified shape area, grouped by the leaf that stores them
+    ///
+    /// Items in the same leaf are spatially close, so keeping them together preserves locality
+    /// for batch processing. Leaves without any matching items are omitted.
+    ///
+    /// **Returns** a vector of each leaf's boundary and its items within the shape
+    pub fn query_grouped<S: Shape>(&self, shape: &S) -> Vec<(Rect, Vec<T>)> {
+        let mut results = vec![];
+        self.root.query_grouped(shape, &mut results, &self.key);
+        results
+    }
+
+    /// Query for items within any of the specified shape areas in a single traversal
+    ///
+    /// Items within more than one of the shapes are only returned once.
+    ///
+    /// **Returns** a vector of items
+    pub fn query_many<S: Shape>(&self, shapes: &[S]) -> Vec<T> {
+        let mut results = vec![];
+        self.root.query_many(shapes, &mut results, &self.key);
+        results
+    }
+}
+
+impl<T, C, K, D> QuadTree<T, C, K, D> {
+    /// Convert the quadtree into one whose clones share their nodes with the original
+    ///
+    /// The nodes are reused as they are, so this takes constant time.
+    ///
+    /// **Returns** the same quadtree with the [`Shared`] policy
+    pub fn into_shared(self) -> QuadTree<T, C, K, D, Shared> {
+        QuadTree {
+            root: self.root,
+            capacity: self.capacity,
+            key: self.key,
+            subdivider: self.subdivider,
+            count: self.count,
+            sharing: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone, C: Clone, K: Clone, D: Clone> QuadTree<T, C, K, D, Shared> {
+    /// Take a snapshot of the quadtree
+    ///
+    /// The snapshot shares its nodes with the original instead of copying them, so this is cheap
+    /// regardless of the size of the tree. Nodes are copied on write when either tree is later
+    /// mutated, leaving the other unaffected.
+    ///
+    /// **Returns** a quadtree with the same contents
+    pub fn snapshot(&self) -> Self {
+        self.clone()
+    }
+}
+
+/// Clone the quadtree, copying its nodes or sharing them as its [`Sharing`] policy requires
+impl<T: Clone, C: Clone, K: Clone, D: Clone, M: Sharing<T>> Clone for QuadTree<T, C, K, D, M> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone_with(&M::clone_child),
+            capacity: self.capacity.clone(),
+            key: self.key.clone(),
+            subdivider: self.subdivider.clone(),
+            count: self.count,
+            sharing: PhantomData,
+        }
+    }
+}
+
+/// Insert every item of the iterator into the QuadTree. Items that fail to insert, such as those
+/// outside of the boundary, are silently dropped.
+impl<T, C, K, D, M> Extend<T> for QuadTree<T, C, K, D, M>
+where
+    C: NodeCapacity,
+    K: KeyFn<T>,
+    D: Subdivider,
+    M: Sharing<T>,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.insert_vec(iter.into_iter().collect());
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T, C, K, D, M> Serialize for QuadTree<T, C, K, D, M>
+where
+    T: Serialize,
+    C: NodeCapacity,
+    K: KeyFn<T>,
+    D: Subdivider,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_region(&self.boundary())
+            .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T, C, K, D, M> QuadTree<T, C, K, D, M>
+where
+    T: Serialize,
+    C: NodeCapacity,
+    K: KeyFn<T>,
+    D: Subdivider,
+{
+    /// Serialize only the items within a specified shape area
+    ///
+    /// This produces the same sequence of items as the `Serialize` implementation, restricted to
+    /// the items [`QuadTree::query`] would return, so a region of a large tree can be
+    /// checkpointed without writing the entire tree. The items are found when this is called,
+    /// and the returned [`Region`] borrows them until it is serialized.
+    ///
+    /// **Returns** a [`Region`] that implements `Serialize`, e.g. for
+    /// `serde_json::to_string(&tree.serialize_region(&rect))`
+    pub fn serialize_region<R: Shape>(&self, shape: &R) -> Region<'_, T> {
+        Region {
+            items: self.query_ref(shape),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize + Point> QuadTree<T> {
+    /// Serialize the quadtree along with its boundary and node capacity
+    ///
+    /// Unlike the `Serialize` implementation, which only writes the items, this produces a
+    /// struct of the form `{ "boundary": {...}, "node_capacity": n, "items": [...] }` that
+    /// [`QuadTree::deserialize_full`] can rebuild the tree from without any other parameters.
+    /// A minimum cell size or maximum depth is not included.
+    ///
+    /// **Returns** an error if the tree was created with a capacity function, which can't be
+    /// serialized
+    pub fn serialize_full<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let node_capacity = self
+            .capacity
+            .fixed_value()
+            .ok_or_else(|| ser::Error::custom("capacity functions cannot be serialized"))?;
+        let mut state = serializer.serialize_struct("QuadTree", 3)?;
+        state.serialize_field("boundary", &self.boundary())?;
+        state.serialize_field("node_capacity", &node_capacity)?;
+        state.serialize_field("items", &self.query_ref(&self.boundary()))?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de> + Point + Clone> QuadTree<T> {
+    /// Deserialize a quadtree written by [`QuadTree::serialize_full`]
+    ///
+    /// **Returns** an error if the input is malformed, or if any item has a non-finite position
+    /// or lies outside of the serialized boundary
+    pub fn deserialize_full<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Boundary {
+            start: P2,
+            end: P2,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(bound = "T: Deserialize<'de>")]
+        struct Full<T> {
+            boundary: Boundary,
+            node_capacity: usize,
+            items: Vec<T>,
+        }
+
+        let Full {
+            boundary,
+            node_capacity,
+            items,
+        } = Full::deserialize(deserializer)?;
+        let mut qt = Self::new(Rect::new(boundary.start, boundary.end), node_capacity);
+        if let Some(item) = qt.insert_vec(items).first() {
+            let point = item.point();
+            let reason = if is_finite(&point) {
+                "is outside of the boundary"
+            } else {
+                "has a non-finite coordinate"
+            };
+            return Err(de::Error::custom(format!(
+                "point ({}, {}) {reason}",
+                point.x, point.y
+            )));
+        }
+
+        Ok(qt)
+    }
+}
+
+#[cfg(feature = "geojson")]
+impl<T, C: NodeCapacity, K: KeyFn<T>, D: Subdivider, M> QuadTree<T, C, K, D, M> {
+    /// Export the stored items as a GeoJSON `FeatureCollection` of `Point` features
+    ///
+    /// Coordinates map x to longitude and y to latitude.
+    ///
+    /// **Returns** the GeoJSON document as a string
+    pub fn to_geojson(&self) -> String {
+        let features = self
+            .query_ref(&self.boundary())
+            .into_iter()
+            .map(|item| {
+                let point = self.key.point(item);
+                serde_json::json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Point",
+                        "coordinates": [point.x, point.y],
+                    },
+                    "properties": {},
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "type": "FeatureCollection",
+            "features": features,
+        })
+        .to_string()
+    }
+}
+
+#[cfg(feature = "geojson")]
+impl QuadTree<P2> {
+    /// Create a new quadtree from a GeoJSON `FeatureCollection` of `Point` features
+    ///
+    /// Coordinates map x to longitude and y to latitude.
+    ///
+    /// ## Arguments
+    /// - `boundary`: The boundary of the quadtree
+    /// - `node_capacity`: The maximum number of items a node can hold before subdividing
+    /// - `s`: The GeoJSON document
+    ///
+    /// **Returns** an error if the document is not a `FeatureCollection` of `Point` features,
+    /// or if any point has a non-finite coordinate or lies outside of `boundary`
+    pub fn from_geojson(
+        boundary: Rect,
+        node_capacity: usize,
+        s: &str,
+    ) -> Result<Self, serde_json::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        #[serde(tag = "type")]
+        enum Geometry {
+            Point { coordinates: [f64; 2] },
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Feature {
+            geometry: Geometry,
+        }
+
+        #[derive(serde::Deserialize)]
+        #[serde(tag = "type")]
+        enum GeoJson {
+            FeatureCollection { features: Vec<Feature> },
+        }
+
+        let GeoJson::FeatureCollection { features } = serde_json::from_str(s)?;
+        let points = features
+            .into_iter()
+            .map(|Feature { geometry }| match geometry {
+                Geometry::Point {
+                    coordinates: [x, y],
+                } => P2::new(x, y),
+            })
+            .collect::<Vec<_>>();
+
+        let mut qt = Self::new(boundary, node_capacity);
+        if let Some(point) = qt.insert_many(&points).first() {
+            let reason = if is_finite(point) {
+                "is outside of the boundary"
+            } else {
+                "has a non-finite coordinate"
+            };
+            return Err(serde_json::Error::custom(format!(
+                "point ({}, {}) {reason}",
+                point.x, point.y
+            )));
+        }
+
+        Ok(qt)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T, C, K, D, M> QuadTree<T, C, K, D, M>
+where
+    T: Clone + Send + Sync,
+    C: NodeCapacity + Sync,
+    K: KeyFn<T> + Sync,
+    D: Subdivider + Sync,
+    M: Sharing<T> + Sync,
+{
+    /// Find the nearest item to each of many points in parallel
+    ///
+    /// Each search is independent and only reads the tree, so they are spread across the rayon
+    /// thread pool.
+    ///
+    /// **Returns** the result of [`QuadTree::nearest`] for each point, in the same order as
+    /// `points`
+    pub fn nearest_batch(&self, points: &[P2]) -> Vec<Option<T>> {
+        points.par_iter().map(|point| self.nearest(point)).collect()
+    }
+}
+
+#[cfg(feature = "svg")]
+impl<T, C: NodeCapacity, K: KeyFn<T>, D: Subdivider, M> QuadTree<T, C, K, D, M> {
+    /// Render the quadtree as an SVG document for debugging
+    ///
+    /// Internal node boundaries are drawn in gray, leaf boundaries in blue, and each stored
+    /// item as a small red circle. The view box is sized from the root boundary.
+    ///
+    /// **Returns** the SVG document as a string
+    pub fn to_svg(&self) -> String {
+        use core::fmt::Write;
+
+        let boundary = self.boundary();
+        let (start, end) = (boundary.start(), boundary.end());
+        let (width, height) = (end.x - start.x, end.y - start.y);
+        let stroke_width = width.max(height) / 500.0;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+            start.x, start.y, width, height
+        );
+        self.root.write_svg(stroke_width, &mut svg, &self.key);
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Depth-first iterator over the non-empty leaves of a quadtree
+struct Leaves<'a, T> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Leaves<'a, T> {
+    type Item = (Rect, &'a [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                Node::External { boundary, data } if !data.is_empty() => {
+                    return Some((*boundary, data));
+                }
+                Node::Internal { children, .. } => {
+                    self.stack.extend(children.iter().rev().map(|c| &**c));
+                }
+                _ => (),
+            }
+        }
+        None
+    }
+}
+
+/// Best-first iterator over the items of a quadtree in order of distance from a point
+struct NearestIter<'a, T, K> {
+    point: P2,
+    key: &'a K,
+    heap: BinaryHeap<NearestEntry<'a, T>>,
+}
+
+impl<'a, T, K: KeyFn<T>> Iterator for NearestIter<'a, T, K> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entry) = self.heap.pop() {
+            match entry.kind {
+                NearestKind::Item(item) => return Some(item),
+                NearestKind::Node(Node::Internal { children, .. }) => {
+                    self.heap.extend(children.iter().map(|child| NearestEntry {
+                        distance_squared: distance_squared_to_rect(&child.boundary(), &self.point),
+                        kind: NearestKind::Node(child),
+                    }));
+                }
+                NearestKind::Node(Node::External { data, .. }) => {
+                    self.heap.extend(data.iter().map(|item| NearestEntry {
+                        distance_squared: na::distance_squared(&self.key.point(item), &self.point),
+                        kind: NearestKind::Item(item),
+                    }));
+                }
+                NearestKind::Node(Node::Empty { .. }) => (),
+            }
+        }
+        None
+    }
+}
+
+/// An entry in the priority queue of a [`NearestIter`]
+///
+/// Entries are ordered so that the closest one is at the top of the max-heap. The distance of a
+/// node is a lower bound for the distances of its items, so items are only yielded once no
+/// unexplored node could contain anything closer.
+struct NearestEntry<'a, T> {
+    distance_squared: f64,
+    kind: NearestKind<'a, T>,
+}
+
+enum NearestKind<'a, T> {
+    Node(&'a Node<T>),
+    Item(&'a T),
+}
+
+impl<T> PartialEq for NearestEntry<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for NearestEntry<'_, T> {}
+
+impl<T> PartialOrd for NearestEntry<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for NearestEntry<'_, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed to pop the closest entry first, with items before nodes at equal distances
+        other
+            .distance_squared
+            .total_cmp(&self.distance_squared)
+            .then_with(|| {
+                let is_item = |entry: &Self| matches!(entry.kind, NearestKind::Item(_));
+                is_item(self).cmp(&is_item(other))
+            })
+    }
+}
+
+/// Get the point where the children of an internal node meet
+fn split_point<T>(children: &[Arc<Node<T>>; 4]) -> P2 {
+    children[0].boundary().end()
+}
+
+/// Get the boundaries of the children of an internal node
+fn child_boundaries<T>(children: &[Arc<Node<T>>; 4]) -> [Rect; 4] {
+    children.each_ref().map(|c| c.boundary())
+}
+
+/// Maximum number of items preallocated for a new leaf, since a capacity may be unbounded
+const MAX_LEAF_PREALLOCATION: usize = 1024;
+
+/// Size of the strong and weak reference counts stored alongside each child node
+const ARC_COUNTERS_SIZE: usize = 2 * core::mem::size_of::<usize>();
+
+/// QuadTree node enum
+///
+/// ## Variants
+/// Children are reference counted so that a tree with the [`Shared`] policy can share its nodes
+/// with its clones. Mutations get a child through the tree's [`Sharing::make_mut`], which copies
+/// a shared node before modifying it, so clones never observe each other's changes. The derived
+/// `Clone` only clones the references to the children.
+///
+/// - `Internal`: Contains children nodes and represents a subdivided area
+/// - `External`: Contains data and represents a leaf node
+/// - `Empty`: Represents an empty area without any data
+#[derive(Debug, Clone)]
+pub enum Node<T> {
+    Internal {
+        boundary: Rect,
+        children: [Arc<Self>; 4],
+    },
+    External {
+        boundary: Rect,
+        data: Vec<T>,
+    },
+    Empty {
+        boundary: Rect,
+    },
+}
+
+impl<T> Node<T> {
+    // Returns the number of subdivisions caused by the insertion
+    fn insert_many<C, D, M, K>(
+        &mut self,
+        mut items: Vec<T>,
+        capacity: &C,
+        subdivider: &D,
+        make_mut: &M,
+        failed: &mut Vec<T>,
+        key: &K,
+    ) -> usize
+    where
+        C: NodeCapacity,
+        D: Subdivider,
+        M: Fn(&mut Arc<Self>) -> &mut Self,
+        K: KeyFn<T>,
+    {
+        match *self {
+            Self::Empty { boundary } => {
+                // Like a single insertion, an empty node takes one item even with zero capacity
+                let cap = capacity.get(&boundary).max(1);
+                if items.len() <= cap {
+                    items
+                        .reserve_exact(cap.min(MAX_LEAF_PREALLOCATION).saturating_sub(items.len()));
+                    *self = Self::External {
+                        boundary,
+                        data: items,
+                    };
+                    0
+                } else {
+                    let children = self.subdivide(subdivider);
+                    *self = Self::Internal { boundary, children };
+                    1 + self.insert_many(items, capacity, subdivider, make_mut, failed, key)
+                }
+            }
+            Self::External {
+                boundary,
+                ref mut data,
+            } => {
+                if data.len() + items.len() <= capacity.get(&boundary).max(1) {
+                    data.extend(items);
+                    return 0;
+                }
+
+                // Reuse the leaf's buffer, which may have spare capacity reserved
+                data.append(&mut items);
+                let items = core::mem::take(data);
+                let children = self.subdivide(subdivider);
+                *self = Self::Internal { boundary, children };
+                1 + self.insert_many(items, capacity, subdivider, make_mut, failed, key)
+            }
+            Self::Internal {
+                boundary,
+                ref mut children,
+            } => {
+                let mut groups =
+                    group_by_quadrant(&boundary, &split_point(children), items, key).into_iter();
+                let mut subdivisions = 0;
+                for c in children {
+                    let items = groups.next().unwrap();
+                    if !items.is_empty() {
+                        subdivisions += make_mut(c)
+                            .insert_many(items, capacity, subdivider, make_mut, failed, key);
+                    }
+                }
+                let cur_failed = groups.next().unwrap();
+                if !cur_failed.is_empty() {
+                    failed.extend(cur_failed);
+                }
+                subdivisions
+            }
+        }
+    }
+
+    fn query_window<'a, K: KeyFn<T>>(
+        &'a self,
+        view: &Rect,
+        items: &mut Vec<&'a T>,
+        cells: &mut Vec<Rect>,
+        key: &K,
+    ) {
+        match self {
+            Self::External { boundary, data } if boundary.intersects(view) => {
+                if view.contains_rect(boundary) {
+                    items.extend(data);
+                } else {
+                    items.extend(data.iter().filter(|item| view.contains(&key.point(item))));
+                }
+                cells.push(*boundary);
+            }
+            Self::Empty { boundary } if boundary.intersects(view) => cells.push(*boundary),
+            Self::Internal { children, .. } => {
+                for q in determine_overlap_quadrants(&child_boundaries(children), view) {
+                    children[q].query_window(view, items, cells, key);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn query_ref<'a, S, F, K: KeyFn<T>>(
+        &'a self,
+        shape: &S,
+        filter: &F,
+        results: &mut Vec<&'a T>,
+        key: &K,
+    ) where
+        S: Shape,
+        F: Fn(&T) -> bool,
+    {
+        match self {
+            Self::External { boundary, data } => {
+                if shape.contains_rect(boundary) {
+                    results.extend(data.iter().filter(|&a| filter(a)));
+                    return;
+                }
+
+                for item in data {
+                    if shape.contains(&key.point(item)) && filter(item) {
+                        results.push(item);
+                    }
+                }
+            }
+            Self::Internal { boundary, children } => {
+                if shape.intersects_rect(boundary) {
+                    for q in determine_overlap_quadrants(&child_boundaries(children), shape) {
+                        children[q].query_ref(shape, filter, results, key);
+                    }
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn any<S, F, K: KeyFn<T>>(&self, shape: &S, predicate: &F, key: &K) -> bool
+    where
+        S: Shape,
+        F: Fn(&T) -> bool,
+    {
+        match self {
+            Self::External { boundary, data } => {
+                if shape.contains_rect(boundary) {
+                    return data.iter().any(predicate);
+                }
+
+                data.iter()
+                    .any(|item| shape.contains(&key.point(item)) && predicate(item))
+            }
+            Self::Internal { boundary, children } => {
+                if shape.intersects_rect(boundary) {
+                    for q in determine_overlap_quadrants(&child_boundaries(children), shape) {
+                        if children[q].any(shape, predicate, key) {
+                            return true;
+                        }
+                    }
+                }
+                false
+            }
+            Self::Empty { .. } => false,
+        }
+    }
+
+    fn find<S: Shape, K: KeyFn<T>>(&self, shape: &S, key: &K) -> Option<&T> {
+        match self {
+            Self::External { data, .. } => {
+                data.iter().find(|item| shape.contains(&key.point(item)))
+            }
+            Self::Internal { boundary, children } => {
+                if !shape.intersects_rect(boundary) {
+                    return None;
+                }
+                determine_overlap_quadrants(&child_boundaries(children), shape)
+                    .into_iter()
+                    .find_map(|q| children[q].find(shape, key))
+            }
+            Self::Empty { .. } => None,
+        }
+    }
+
+    fn centroid<S: Shape, K: KeyFn<T>>(
+        &self,
+        shape: &S,
+        sum: &mut Vector2<f64>,
+        count: &mut usize,
+        key: &K,
+    ) {
+        match self {
+            Self::External { boundary, data } => {
+                if shape.contains_rect(boundary) {
+                    *sum += data
+                        .iter()
+                        .map(|item| key.point(item).coords)
+                        .sum::<Vector2<f64>>();
+                    *count += data.len();
+                } else {
+                    for item in data {
+                        let point = key.point(item);
+                        if shape.contains(&point) {
+                            *sum += point.coords;
+                            *count += 1;
+                        }
+                    }
+                }
+            }
+            Self::Internal { boundary, children } => {
+                if shape.intersects_rect(boundary) {
+                    for q in determine_overlap_quadrants(&child_boundaries(children), shape) {
+                        children[q].centroid(shape, sum, count, key);
+                    }
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn density_grid<F, K: KeyFn<T>>(
+        &self,
+        region: &Rect,
+        cols: usize,
+        rows: usize,
+        filter: &F,
+        grid: &mut [usize],
+        key: &K,
+    ) where
+        F: Fn(&T) -> bool,
+    {
+        match self {
+            Self::External { data, .. } => {
+                for item in data {
+                    let point = key.point(item);
+                    if region.contains(&point) && filter(item) {
+                        grid[grid_cell(region, cols, rows, &point)] += 1;
+                    }
+                }
+            }
+            Self::Internal { boundary, children } => {
+                if boundary.intersects(region) {
+                    for q in determine_overlap_quadrants(&child_boundaries(children), region) {
+                        children[q].density_grid(region, cols, rows, filter, grid, key);
+                    }
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn quadrant_counts<K: KeyFn<T>>(&self, region: &Rect, counts: &mut [usize; 4], key: &K) {
+        match self {
+            Self::External { data, .. } => {
+                for item in data {
+                    if let Some(q) = determine_quadrant(region, &key.point(item)) {
+                        counts[q] += 1;
+                    }
+                }
+            }
+            Self::Internal { boundary, children } => {
+                if boundary.intersects(region) {
+                    for q in determine_overlap_quadrants(&child_boundaries(children), region) {
+                        children[q].quadrant_counts(region, counts, key);
+                    }
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            Self::Internal { children, .. } => {
+                1 + children.iter().map(|c| c.depth()).max().unwrap_or(0)
+            }
+            Self::External { .. } | Self::Empty { .. } => 0,
+        }
+    }
+
+    fn cell_of(&self, point: &P2) -> Option<Rect> {
+        match self {
+            Self::Internal { boundary, children } => {
+                children[split_quadrant(boundary, &split_point(children), point)?].cell_of(point)
+            }
+            Self::External { boundary, .. } | Self::Empty { boundary } => {
+                boundary.contains(point).then_some(*boundary)
+            }
+        }
+    }
+
+    // Updates `best` with the nearest matching item and its squared distance if closer than the
+    // current best. Nodes are pruned unless they could be closer than `shrink` times the best
+    // squared distance.
+    fn nearest<'a, F: Fn(&T) -> bool, K: KeyFn<T>>(
+        &'a self,
+        point: &P2,
+        filter: &F,
+        shrink: f64,
+        best: &mut Option<(&'a T, f64)>,
+        key: &K,
+    ) {
+        match self {
+            Self::External { data, .. } => {
+                for item in data.iter().filter(|item| filter(item)) {
+                    let distance = na::distance_squared(&key.point(item), point);
+                    if best.is_none_or(|(_, d)| distance < d) {
+                        *best = Some((item, distance));
+                    }
+                }
+            }
+            Self::Internal { children, .. } => {
+                let mut order = children
+                    .each_ref()
+                    .map(|c| (distance_squared_to_rect(&c.boundary(), point), c));
+                order.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (min_distance, c) in order {
+                    if best.is_some_and(|(_, d)| min_distance > d * shrink) {
+                        break;
+                    }
+                    c.nearest(point, filter, shrink, best, key);
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn closest_to<'a, K: KeyFn<T>>(
+        &'a self,
+        item: &'a T,
+        best: &mut Option<(&'a T, &'a T, f64)>,
+        key: &K,
+    ) {
+        let point = key.point(item);
+        if best.is_some_and(|(_, _, d)| distance_squared_to_rect(&self.boundary(), &point) >= d) {
+            return;
+        }
+
+        match self {
+            Self::External { data, .. } => {
+                for other in data {
+                    if core::ptr::eq(item, other) {
+                        continue;
+                    }
+                    let distance = na::distance_squared(&point, &key.point(other));
+                    if best.is_none_or(|(_, _, d)| distance < d) {
+                        *best = Some((item, other, distance));
+                    }
+                }
+            }
+            Self::Internal { children, .. } => {
+                for c in children {
+                    c.closest_to(item, best, key);
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn raycast<'a, K: KeyFn<T>>(
+        &'a self,
+        ray: &Ray,
+        tolerance: f64,
+        best: &mut Option<(&'a T, f64)>,
+        key: &K,
+    ) {
+        match self {
+            Self::External { data, .. } => {
+                for item in data {
+                    let (along, across) = ray.project(&key.point(item));
+                    if along >= 0.0 && across <= tolerance && best.is_none_or(|(_, d)| along < d) {
+                        *best = Some((item, along));
+                    }
+                }
+            }
+            Self::Internal { children, .. } => {
+                // An item within tolerance of the ray projects onto it no earlier than where the
+                // ray enters the item's cell expanded by the tolerance
+                let mut order: Vec<_> = children
+                    .iter()
+                    .filter_map(|c| {
+                        let entry = ray.entry_distance(&expand_rect(&c.boundary(), tolerance))?;
+                        Some((entry, c))
+                    })
+                    .collect();
+                order.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (entry, c) in order {
+                    if best.is_some_and(|(_, d)| entry > d) {
+                        break;
+                    }
+                    c.raycast(ray, tolerance, best, key);
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn map<U, F>(&self, f: &F) -> Node<U>
+    where
+        F: Fn(&T) -> U,
+    {
+        match self {
+            Self::External { boundary, data } => Node::External {
+                boundary: *boundary,
+                data: data.iter().map(f).collect(),
+            },
+            Self::Internal { boundary, children } => Node::Internal {
+                boundary: *boundary,
+                children: children.each_ref().map(|c| Arc::new(c.map(f))),
+            },
+            Self::Empty { boundary } => Node::Empty {
+                boundary: *boundary,
+            },
+        }
+    }
+
+    fn validate<C: NodeCapacity, D: Subdivider, K: KeyFn<T>>(
+        &self,
+        capacity: &C,
+        subdivider: &D,
+        count: &mut usize,
+        key: &K,
+    ) -> Result<(), String> {
+        match self {
+            Self::External { boundary, data } => {
+                // An empty node always takes its first item, even if its capacity is zero
+                let cap = capacity.get(boundary).max(1);
+                if data.len() > cap {
+                    return Err(format!(
+                        "leaf {:?} holds {} items but its capacity is {}",
+                        boundary,
+                        data.len(),
+                        cap
+                    ));
+                }
+                if let Some(item) = data
+                    .iter()
+                    .find(|item| !boundary.contains(&key.point(item)))
+                {
+                    return Err(format!(
+                        "item at {} lies outside of its leaf {:?}",
+                        key.point(item),
+                        boundary
+                    ));
+                }
+                *count += data.len();
+            }
+            Self::Internal { boundary, children } => {
+                for (c, quarter) in children.iter().zip(subdivider.subdivide(boundary)) {
+                    if c.boundary() != quarter {
+                        return Err(format!(
+                            "child {:?} does not subdivide its parent {:?}",
+                            c.boundary(),
+                            boundary
+                        ));
+                    }
+                    c.validate(capacity, subdivider, count, key)?;
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+        Ok(())
+    }
+
+    // Returns the heap memory owned by this node and its descendants
+    fn heap_size(&self) -> usize {
+        match self {
+            Self::External { data, .. } => data.capacity() * core::mem::size_of::<T>(),
+            Self::Internal { children, .. } => children
+                .iter()
+                .map(|c| ARC_COUNTERS_SIZE + core::mem::size_of::<Self>() + c.heap_size())
+                .sum(),
+            Self::Empty { .. } => 0,
+        }
+    }
+
+    fn walk<V: NodeVisitor<T>>(&self, depth: usize, visitor: &mut V) {
+        match self {
+            Self::Internal { boundary, children } => {
+                visitor.visit_internal(*boundary, depth);
+                for c in children {
+                    c.walk(depth + 1, visitor);
+                }
+            }
+            Self::External { boundary, data } => visitor.visit_leaf(*boundary, depth, data),
+            Self::Empty { boundary } => visitor.visit_leaf(*boundary, depth, &[]),
+        }
+    }
+
+    fn node_boundaries(&self, depth: usize, results: &mut Vec<(Rect, usize)>) {
+        results.push((self.boundary(), depth));
+        if let Self::Internal { children, .. } = self {
+            for c in children {
+                c.node_boundaries(depth + 1, results);
+            }
+        }
+    }
+
+    #[cfg(feature = "svg")]
+    fn write_svg<K: KeyFn<T>>(&self, stroke_width: f64, svg: &mut String, key: &K) {
+        use core::fmt::Write;
+
+        let boundary = self.boundary();
+        let (start, end) = (boundary.start(), boundary.end());
+        let stroke = match self {
+            Self::Internal { .. } => "gray",
+            _ => "blue",
+        };
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="{}" stroke-width="{}"/>"#,
+            start.x,
+            start.y,
+            end.x - start.x,
+            end.y - start.y,
+            stroke,
+            stroke_width
+        );
+
+        match self {
+            Self::External { data, .. } => {
+                for item in data {
+                    let point = key.point(item);
+                    let _ = writeln!(
+                        svg,
+                        r#"<circle cx="{}" cy="{}" r="{}" fill="red"/>"#,
+                        point.x,
+                        point.y,
+                        stroke_width * 2.0
+                    );
+                }
+            }
+            Self::Internal { children, .. } => {
+                for c in children {
+                    c.write_svg(stroke_width, svg, key);
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn center(&self) -> P2 {
+        self.boundary().center()
+    }
+
+    const fn boundary(&self) -> Rect {
+        match self {
+            Self::Empty { boundary } => *boundary,
+            Self::External { boundary, .. } => *boundary,
+            Self::Internal { boundary, .. } => *boundary,
+        }
+    }
+
+    fn subdivide<D: Subdivider>(&self, subdivider: &D) -> [Arc<Self>; 4] {
+        let rects = subdivider.subdivide(&self.boundary());
+        rects.map(|r| Arc::new(Self::Empty { boundary: r }))
+    }
+
+    // Returns the number of subdivisions caused by the insertion
+    fn insert<C, D, M, F, K>(
+        &mut self,
+        item: T,
+        capacity: &C,
+        subdivider: &D,
+        make_mut: &M,
+        on_relocate: &mut F,
+        key: &K,
+    ) -> Result<usize, T>
+    where
+        C: NodeCapacity,
+        D: Subdivider,
+        M: Fn(&mut Arc<Self>) -> &mut Self,
+        F: FnMut(&T),
+        K: KeyFn<T>,
+    {
+        let point = key.point(&item);
+
+        if !self.boundary().contains(&point) {
+            return Err(item);
+        }
+
+        match *self {
+            Self::Empty { boundary } => {
+                let cap = capacity.get(&boundary).min(MAX_LEAF_PREALLOCATION);
+                let mut data = Vec::with_capacity(cap);
+                data.push(item);
+                *self = Self::External { boundary, data };
+                Ok(0)
+            }
+            Self::External {
+                boundary,
+                ref mut data,
+            } => {
+                if data.len() < capacity.get(&boundary) {
+                    data.push(item);
+                    return Ok(0);
+                }
+
+                let mut data = core::mem::take(data);
+                data.iter().for_each(&mut *on_relocate);
+                data.push(item);
+                let children = self.subdivide(subdivider);
+                *self = Self::Internal { boundary, children };
+
+                // Every item lies within this node's boundary, so each fits in one of the children
+                let mut failed = Vec::new();
+                let subdivisions =
+                    self.insert_many(data, capacity, subdivider, make_mut, &mut failed, key);
+                debug_assert!(failed.is_empty());
+                Ok(1 + subdivisions)
+            }
+            Self::Internal {
+                boundary,
+                ref mut children,
+            } => match split_quadrant(&boundary, &split_point(children), &point) {
+                Some(q) => make_mut(&mut children[q]).insert(
+                    item,
+                    capacity,
+                    subdivider,
+                    make_mut,
+                    on_relocate,
+                    key,
+                ),
+                None => Err(item),
+            },
+        }
+    }
+
+    // Removes the first item at an exact position.
+    // Returns the item and whether the node is empty after removal.
+    fn remove<F, M, K>(
+        &mut self,
+        point: &P2,
+        matches: &F,
+        make_mut: &M,
+        key: &K,
+    ) -> Option<(T, bool)>
+    where
+        F: Fn(&T) -> bool,
+        M: Fn(&mut Arc<Self>) -> &mut Self,
+        K: KeyFn<T>,
+    {
+        match *self {
+            Self::External {
+                boundary,
+                ref mut data,
+            } => {
+                let index = data
+                    .iter()
+                    .position(|item| key.point(item) == *point && matches(item))?;
+                let item = data.swap_remove(index);
+                if data.is_empty() {
+                    *self = Self::Empty { boundary };
+                    return Some((item, true));
+                }
+                Some((item, false))
+            }
+            Self::Internal {
+                boundary,
+                ref mut children,
+            } => {
+                let q = split_quadrant(&boundary, &split_point(children), point)?;
+                let (item, is_empty) =
+                    make_mut(&mut children[q]).remove(point, matches, make_mut, key)?;
+                if is_empty && children.iter().all(|c| matches!(**c, Self::Empty { .. })) {
+                    *self = Self::Empty { boundary };
+                    return Some((item, true));
+                }
+                Some((item, false))
+            }
+            Self::Empty { .. } => None,
+        }
+    }
+
+    fn replace<M, K: KeyFn<T>>(
+        &mut self,
+        point: &P2,
+        new_item: T,
+        make_mut: &M,
+        key: &K,
+    ) -> Option<T>
+    where
+        M: Fn(&mut Arc<Self>) -> &mut Self,
+    {
+        match self {
+            Self::External { data, .. } => data
+                .iter_mut()
+                .find(|item| key.point(item) == *point)
+                .map(|item| core::mem::replace(item, new_item)),
+            Self::Internal { boundary, children } => {
+                match split_quadrant(boundary, &split_point(children), point) {
+                    Some(q) => make_mut(&mut children[q]).replace(point, new_item, make_mut, key),
+                    None => None,
+                }
+            }
+            Self::Empty { .. } => None,
+        }
+    }
+
+    // Returns true if the node is empty after deletion
+    fn delete<S, F, M, K: KeyFn<T>>(
+        &mut self,
+        shape: &S,
+        filter: &F,
+        deleted: &mut usize,
+        make_mut: &M,
+        key: &K,
+    ) -> bool
+    where
+        S: Shape,
+        F: Fn(&T) -> bool,
+        M: Fn(&mut Arc<Self>) -> &mut Self,
+    {
+        match *self {
+            Self::External {
+                boundary,
+                ref mut data,
+            } => {
+                if !shape.intersects_rect(&boundary) {
+                    return false;
+                }
+
+                let original_data_len = data.len();
+                data.retain(|item| !(shape.contains(&key.point(item)) && filter(item)));
+                *deleted += original_data_len - data.len();
+
+                if data.is_empty() {
+                    *self = Self::Empty { boundary };
+                    return true;
+                }
+
+                false
+            }
+            Self::Internal {
+                boundary,
+                ref mut children,
+            } => {
+                if shape.intersects_rect(&boundary) {
+                    let mut is_all_empty = true;
+                    for c in children {
+                        // Skip untouched children so subtrees shared with a clone aren't copied
+                        let is_empty = if shape.intersects_rect(&c.boundary()) {
+                            make_mut(c).delete(shape, filter, deleted, make_mut, key)
+                        } else {
+                            matches!(**c, Self::Empty { .. })
+                        };
+                        if !is_empty {
+                            is_all_empty = false;
+                        }
+                    }
+                    if is_all_empty {
+                        *self = Self::Empty { boundary };
+                        return true;
+                    }
+                }
+
+                false
+            }
+            Self::Empty { .. } => true,
+        }
+    }
+
+    // Returns true if the node is empty after deletion
+    fn delete_many<S: Shape, M, K: KeyFn<T>>(
+        &mut self,
+        shapes: &[S],
+        deleted: &mut usize,
+        make_mut: &M,
+        key: &K,
+    ) -> bool
+    where
+        M: Fn(&mut Arc<Self>) -> &mut Self,
+    {
+        match *self {
+            Self::External {
+                boundary,
+                ref mut data,
+            } => {
+                if !intersects_any(&boundary, shapes) {
+                    return false;
+                }
+
+                let original_data_len = data.len();
+                data.retain(|item| !contains_any(shapes, &key.point(item)));
+                *deleted += original_data_len - data.len();
+
+                if data.is_empty() {
+                    *self = Self::Empty { boundary };
+                    return true;
+                }
+
+                false
+            }
+            Self::Internal {
+                boundary,
+                ref mut children,
+            } => {
+                if intersects_any(&boundary, shapes) {
+                    let mut is_all_empty = true;
+                    for c in children {
+                        let is_empty = if intersects_any(&c.boundary(), shapes) {
+                            make_mut(c).delete_many(shapes, deleted, make_mut, key)
+                        } else {
+                            matches!(**c, Self::Empty { .. })
+                        };
+                        if !is_empty {
+                            is_all_empty = false;
+                        }
+                    }
+                    if is_all_empty {
+                        *self = Self::Empty { boundary };
+                        return true;
+                    }
+                }
+
+                false
+            }
+            Self::Empty { .. } => true,
+        }
+    }
+
+    // Returns true if the node is empty after deletion
+    fn pop<S, F, G, M, K: KeyFn<T>>(
+        &mut self,
+        shape: &S,
+        filter: &F,
+        sink: &mut G,
+        make_mut: &M,
+        key: &K,
+    ) -> bool
+    where
+        S: Shape,
+        F: Fn(&T) -> bool,
+        G: FnMut(T),
+        M: Fn(&mut Arc<Self>) -> &mut Self,
+    {
+        match *self {
+            Self::External {
+                boundary,
+                ref mut data,
+            } => {
+                if !shape.intersects_rect(&boundary) {
+                    return false;
+                }
+
+                let mut left_data = Vec::with_capacity(data.capacity());
+                for item in data.drain(..) {
+                    if shape.contains(&key.point(&item)) && filter(&item) {
+                        sink(item);
+                    } else {
+                        left_data.push(item);
+                    }
+                }
+
+                if left_data.is_empty() {
+                    *self = Self::Empty { boundary };
+                    true
+                } else {
+                    *data = left_data;
+                    false
+                }
+            }
+            Self::Internal {
+                boundary,
+                ref mut children,
+            } => {
+                if shape.intersects_rect(&boundary) {
+                    let mut is_all_empty = true;
+                    for c in children {
+                        // Skip untouched children so subtrees shared with a clone aren't copied
+                        let is_empty = if shape.intersects_rect(&c.boundary()) {
+                            make_mut(c).pop(shape, filter, sink, make_mut, key)
+                        } else {
+                            matches!(**c, Self::Empty { .. })
+                        };
+                        if !is_empty {
+                            is_all_empty = false;
+                        }
+                    }
+                    if is_all_empty {
+                        *self = Self::Empty { boundary };
+                        return true;
+                    }
+                }
+
+                false
+            }
+            Self::Empty { .. } => true,
+        }
+    }
+
+    fn into_items<M>(self, make_mut: &M, items: &mut Vec<T>)
+    where
+        M: Fn(&mut Arc<Self>) -> &mut Self,
+    {
+        match self {
+            Self::External { mut data, .. } => items.append(&mut data),
+            Self::Internal { children, .. } => {
+                for mut c in children {
+                    let child = make_mut(&mut c);
+                    let boundary = child.boundary();
+                    core::mem::replace(child, Self::Empty { boundary }).into_items(make_mut, items);
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        match *self {
+            Self::External {
+                boundary,
+                ref mut data,
+            } => {
+                if data.is_empty() {
+                    *self = Self::Empty { boundary };
+                } else {
+                    data.shrink_to_fit();
+                }
+            }
+            Self::Internal {
+                ref mut children, ..
+            } => {
+                // Subtrees shared with a clone are left alone rather than copied to be shrunk
+                for c in children.iter_mut().filter_map(Arc::get_mut) {
+                    c.shrink_to_fit();
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+}
+
+impl<T: Clone> Node<T> {
+    // Clones the node, getting each of its children from `clone_child`
+    pub(crate) fn clone_with<F>(&self, clone_child: &F) -> Self
+    where
+        F: Fn(&Arc<Self>) -> Arc<Self>,
+    {
+        match self {
+            Self::Internal { boundary, children } => Self::Internal {
+                boundary: *boundary,
+                children: children.each_ref().map(clone_child),
+            },
+            Self::External { boundary, data } => Self::External {
+                boundary: *boundary,
+                data: data.clone(),
+            },
+            Self::Empty { boundary } => Self::Empty {
+                boundary: *boundary,
+            },
+        }
+    }
+
+    fn query<S, F, K: KeyFn<T>>(
+        &self,
+        shape: &S,
+        filter: &F,
+        results: &mut Vec<T>,
+        stats: &mut QueryStats,
+        key: &K,
+    ) where
+        S: Shape + ?Sized,
+        F: Fn(&T) -> bool,
+    {
+        stats.nodes_visited += 1;
+        match self {
+            Self::External { boundary, data } => {
+                stats.leaves_visited += 1;
+                stats.items_examined += data.len();
+                if shape.contains_rect(boundary) {
+                    results.extend(data.iter().filter(|&a| filter(a)).cloned());
+                } else {
+                    for item in data {
+                        if shape.contains(&key.point(item)) && filter(item) {
+                            results.push(item.clone());
+                        }
+                    }
+                }
+            }
+            Self::Internal { boundary, children } => {
+                if shape.intersects_rect(boundary) {
+                    for q in determine_overlap_quadrants(&child_boundaries(children), shape) {
+                        children[q].query(shape, filter, results, stats, key);
+                    }
+                }
+            }
+            Self::Empty { .. } => stats.leaves_visited += 1,
+        }
+    }
+
+    fn query_grouped<S: Shape, K: KeyFn<T>>(
+        &self,
+        shape: &S,
+        results: &mut Vec<(Rect, Vec<T>)>,
+        key: &K,
+    ) {
+        match self {
+            Self::External { boundary, data } => {
+                let items = if shape.contains_rect(boundary) {
+                    data.clone()
+                } else {
+                    data.iter()
+                        .filter(|item| shape.contains(&key.point(item)))
+                        .cloned()
+                        .collect::<Vec<_>>()
+                };
+                if !items.is_empty() {
+                    results.push((*boundary, items));
+                }
+            }
+            Self::Internal { boundary, children } => {
+                if shape.intersects_rect(boundary) {
+                    for q in determine_overlap_quadrants(&child_boundaries(children), shape) {
+                        children[q].query_grouped(shape, results, key);
+                    }
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn query_with_depth<S: Shape, K: KeyFn<T>>(
+        &self,
+        shape: &S,
+        depth: usize,
+        results: &mut Vec<(T, usize)>,
+        key: &K,
+    ) {
+        match self {
+            Self::External { boundary, data } => {
+                if shape.contains_rect(boundary) {
+                    results.extend(data.iter().map(|item| (item.clone(), depth)));
+                } else {
+                    for item in data {
+                        if shape.contains(&key.point(item)) {
+                            results.push((item.clone(), depth));
+                        }
+                    }
+                }
+            }
+            Self::Internal { boundary, children } => {
+                if shape.intersects_rect(boundary) {
+                    for q in determine_overlap_quadrants(&child_boundaries(children), shape) {
+                        children[q].query_with_depth(shape, depth + 1, results, key);
+                    }
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn query_outside<S: Shape, K: KeyFn<T>>(&self, shape: &S, results: &mut Vec<T>, key: &K) {
+        if shape.contains_rect(&self.boundary()) {
+            return;
+        }
+
+        match self {
+            Self::External { data, .. } => {
+                results.extend(
+                    data.iter()
+                        .filter(|item| !shape.contains(&key.point(item)))
+                        .cloned(),
+                );
+            }
+            Self::Internal { children, .. } => {
+                for c in children {
+                    c.query_outside(shape, results, key);
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    /// **Returns** `true` once `limit` items have been collected
+    fn query_limit<S: Shape, K: KeyFn<T>>(
+        &self,
+        shape: &S,
+        limit: usize,
+        results: &mut Vec<T>,
+        key: &K,
+    ) -> bool {
+        match self {
+            Self::External { boundary, data } => {
+                let remaining = limit - results.len();
+                if shape.contains_rect(boundary) {
+                    results.extend(data.iter().take(remaining).cloned());
+                } else {
+                    results.extend(
+                        data.iter()
+                            .filter(|item| shape.contains(&key.point(item)))
+                            .take(remaining)
+                            .cloned(),
+                    );
+                }
+                results.len() == limit
+            }
+            Self::Internal { boundary, children } => {
+                if shape.intersects_rect(boundary) {
+                    for q in determine_overlap_quadrants(&child_boundaries(children), shape) {
+                        if children[q].query_limit(shape, limit, results, key) {
+                            return true;
+                        }
+                    }
+                }
+                false
+            }
+            Self::Empty { .. } => false,
+        }
+    }
+
+    fn query_many<S: Shape, K: KeyFn<T>>(&self, shapes: &[S], results: &mut Vec<T>, key: &K) {
+        if !intersects_any(&self.boundary(), shapes) {
+            return;
+        }
+
+        match self {
+            Self::External { data, .. } => {
+                for item in data {
+                    if contains_any(shapes, &key.point(item)) {
+                        results.push(item.clone());
+                    }
+                }
+            }
+            Self::Internal { children, .. } => {
+                for c in children {
+                    c.query_many(shapes, results, key);
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn get_many<K: KeyFn<T>>(&self, points: Vec<(usize, P2)>, results: &mut [Option<T>], key: &K) {
+        match self {
+            Self::External { data, .. } => {
+                for (i, point) in points {
+                    results[i] = data.iter().find(|item| key.point(item) == point).cloned();
+                }
+            }
+            Self::Internal { boundary, children } => {
+                let mut groups: [Vec<(usize, P2)>; 4] = Default::default();
+                for (i, point) in points {
+                    if let Some(q) = split_quadrant(boundary, &split_point(children), &point) {
+                        groups[q].push((i, point));
+                    }
+                }
+                for (c, group) in children.iter().zip(groups) {
+                    if !group.is_empty() {
+                        c.get_many(group, results, key);
+                    }
+                }
+            }
+            Self::Empty { .. } => (),
+        }
+    }
+
+    fn get<K: KeyFn<T>>(&self, point: &P2, key: &K) -> Option<T> {
+        match self {
+            Self::External { data, .. } => {
+                for item in data {
+                    if key.point(item) == *point {
+                        return Some(item.clone());
+                    }
+                }
+                None
+            }
+            Self::Internal { boundary, children } => {
+                match split_quadrant(boundary, &split_point(children), point) {
+                    Some(q) => children[q].get(point, key),
+                    None => None,
+                }
+            }
+            Self::Empty { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::{
+        shapes::{Circle, HalfPlane},
+        util::tests::{make_circle, make_rect},
+        Point, SharedQuadTree,
+    };
+
+    use super::*;
+
+    #[test]
+    fn insert_single_item() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let item = point![25.0, 25.0];
+        assert!(qt.insert(&item), "Should insert item successfully");
+    }
+
+    #[test]
+    fn insert_multiple_items() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![
+            point![10.0, 10.0],
+            point![150.0, 150.0], // This should fail (out of bounds)
+            point![20.0, 20.0],
+            point![120.0, 120.0], // This should fail (out of bounds)
+        ];
+
+        let failed_inserts = qt.insert_many(&points);
+
+        assert_eq!(failed_inserts.len(), 2, "Should return 2 failed inserts");
+        assert!(
+            failed_inserts.contains(&points[1]),
+            "Should include point (150, 150) as failed"
+        );
+        assert!(
+            failed_inserts.contains(&points[3]),
+            "Should include point (120, 120) as failed"
+        );
+
+        // Ensure that successful points are indeed in the QuadTree
+        assert!(
+            qt.get(&points[0].point()).is_some(),
+            "Point (10, 10) should be successfully inserted"
+        );
+        assert!(
+            qt.get(&points[2].point()).is_some(),
+            "Point (20, 20) should be successfully inserted"
+        );
+
+        // Check count correctness
+        assert_eq!(
+            qt.count(),
+            2,
+            "Count should be 2 for successfully inserted items"
+        );
+    }
+
+    #[test]
+    fn extend_from_slice_items() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = [
+            point![10.0, 10.0],
+            point![150.0, 150.0], // This should fail (out of bounds)
+            point![20.0, 20.0],
+            point![120.0, 120.0], // This should fail (out of bounds)
+        ];
+
+        let failed_inserts = qt.extend_from_slice(&points);
+
+        assert_eq!(failed_inserts.len(), 2, "Should return 2 failed inserts");
+        assert!(
+            failed_inserts.contains(&points[1]),
+            "Should include point (150, 150) as failed"
+        );
+        assert!(
+            failed_inserts.contains(&points[3]),
+            "Should include point (120, 120) as failed"
+        );
+        assert!(
+            qt.get(&points[0]).is_some(),
+            "Point (10, 10) should be successfully inserted"
+        );
+        assert!(
+            qt.get(&points[2]).is_some(),
+            "Point (20, 20) should be successfully inserted"
+        );
+        assert_eq!(
+            qt.count(),
+            2,
+            "Count should be 2 for successfully inserted items"
+        );
+    }
+
+    #[test]
+    fn insert_item_out_of_bounds() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let item = point![150.0, 150.0];
+        assert!(!qt.insert(&item), "Should not insert item outside bounds");
+    }
+
+    #[test]
+    fn insert_owned_item() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        assert_eq!(
+            qt.insert_owned(point![25.0, 25.0]),
+            Ok(()),
+            "Should insert item successfully"
+        );
+        assert_eq!(
+            qt.insert_owned(point![150.0, 150.0]),
+            Err(point![150.0, 150.0]),
+            "Should return the item outside bounds"
+        );
+        assert_eq!(qt.count(), 1, "Only the inserted item should be counted");
+        assert!(
+            qt.get(&point![25.0, 25.0]).is_some(),
+            "Inserted item should be found"
+        );
+    }
+
+    #[test]
+    fn insert_owned_without_clone() {
+        #[derive(Debug, PartialEq)]
+        struct Payload {
+            position: P2,
+            data: Vec<u8>,
+        }
+
+        impl Point for Payload {
+            fn point(&self) -> P2 {
+                self.position
+            }
+        }
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        for i in 0..4 {
+            let payload = Payload {
+                position: point![10.0 + 20.0 * i as f64, 50.0],
+                data: vec![i; 16],
+            };
+            assert_eq!(
+                qt.insert_owned(payload),
+                Ok(()),
+                "Should insert an item that isn't Clone"
+            );
+        }
+
+        let outside = Payload {
+            position: point![150.0, 50.0],
+            data: vec![9; 16],
+        };
+        assert_eq!(
+            qt.insert_owned(outside),
+            Err(Payload {
+                position: point![150.0, 50.0],
+                data: vec![9; 16],
+            }),
+            "Should return the original item outside bounds"
+        );
+        assert_eq!(qt.count(), 4, "Only the inserted items should be counted");
+        assert_eq!(
+            qt.query_ref(&make_rect(25.0, 0.0, 55.0, 100.0))
+                .iter()
+                .map(|p| p.data[0])
+                .collect::<Vec<_>>(),
+            vec![1, 2],
+            "Inserted items should be found by reference"
+        );
+        assert_eq!(qt.validate(), Ok(()));
+    }
+
+    #[test]
+    fn insert_non_finite_item() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        assert!(
+            !qt.insert(&point![f64::NAN, 50.0]),
+            "Should not insert item with a NaN coordinate"
+        );
+        assert!(
+            !qt.insert(&point![50.0, f64::INFINITY]),
+            "Should not insert item with an infinite coordinate"
+        );
+
+        let failed = qt.insert_many(&[point![f64::NAN, 10.0], point![10.0, 10.0]]);
+        assert_eq!(failed.len(), 1, "Should return the NaN item as failed");
+        assert_eq!(qt.count(), 1, "Only the finite item should be stored");
+    }
+
+    #[test]
+    fn with_capacity_and_reserve() {
+        let mut qt = QuadTree::with_capacity(make_rect(0.0, 0.0, 100.0, 100.0), 2, 8);
+        let points = (1..=8)
+            .map(|i| point![i as f64 * 10.0, i as f64 * 10.0])
+            .collect::<Vec<_>>();
+        assert!(
+            qt.insert_many(&points).is_empty(),
+            "All points should be inserted"
+        );
+        assert_eq!(qt.count(), 8, "Count should include all inserted points");
+        assert_eq!(
+            qt.query(&qt.boundary()).len(),
+            8,
+            "All points should be queryable"
+        );
+
+        qt.reserve(100);
+        assert!(
+            qt.insert(&point![5.0, 5.0]),
+            "Should insert after reserving"
+        );
+        assert_eq!(qt.count(), 9, "Count should be unchanged by reserving");
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.reserve(10);
+        assert!(
+            qt.query(&qt.boundary()).is_empty(),
+            "Reserving should not add items"
+        );
+        assert!(
+            qt.insert(&point![5.0, 5.0]),
+            "Should insert after reserving"
+        );
+        assert_eq!(qt.delete(&qt.boundary()), 1, "Should delete the item");
+    }
+
+    #[test]
+    fn extend_from_iterator() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        qt.insert(&point![50.0, 50.0]);
+        qt.extend((0..15).map(|i| point![i as f64 * 10.0, 25.0]));
+
+        assert_eq!(
+            qt.count(),
+            12,
+            "Count should include the 11 points within bounds"
+        );
+        assert_eq!(
+            qt.query(&make_rect(0.0, 20.0, 100.0, 30.0)).len(),
+            11,
+            "Extended points should be queryable"
+        );
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 64);
+        qt.extend((0..15).map(|i| point![i as f64 * 10.0, 25.0]));
+        assert_eq!(
+            qt.count(),
+            11,
+            "Points outside bounds should be dropped without subdividing"
+        );
+        assert_eq!(
+            qt.query(&make_rect(100.0, 0.0, 200.0, 100.0)).len(),
+            1,
+            "Only the point on the edge should be stored at x >= 100"
+        );
+    }
+
+    #[test]
+    fn insert_multiple_items_subdivision() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let points = vec![point![20.0, 20.0], point![40.0, 40.0], point![60.0, 60.0]];
+
+        qt.insert_many(&points);
+
+        match qt.root {
+            Node::Internal { children, .. } => {
+                assert_eq!(
+                    children.len(),
+                    4,
+                    "Should have four children after subdivision"
+                );
+            }
+            _ => panic!("QuadTree should have subdivided into an internal node"),
+        }
+    }
+
+    #[test]
+    fn capacity_fn_shallower_cluster() {
+        let boundary = make_rect(0.0, 0.0, 100.0, 100.0);
+        let mut fixed = QuadTree::new(boundary, 2);
+        let mut adaptive = QuadTree::with_capacity_fn(boundary, |r| {
+            if r.end().x - r.start().x <= 50.0 {
+                64
+            } else {
+                2
+            }
+        });
+        let cluster = (0..20)
+            .map(|i| point![10.0 + i as f64 * 0.05, 10.0 + i as f64 * 0.05])
+            .collect::<Vec<_>>();
+        fixed.insert_many(&cluster);
+        for point in &cluster {
+            adaptive.insert(point);
+        }
+
+        let max_depth = |qt: &QuadTree<P2>| {
+            qt.node_boundaries()
+                .into_iter()
+                .map(|(_, depth)| depth)
+                .max()
+                .unwrap()
+        };
+        assert_eq!(
+            max_depth(&adaptive),
+            1,
+            "Adaptive tree should stop at depth 1"
+        );
+        assert!(
+            max_depth(&fixed) > max_depth(&adaptive),
+            "Fixed small capacity should produce a deeper tree"
+        );
+        assert_eq!(adaptive.count(), 20, "All points should be inserted");
+        assert_eq!(
+            adaptive.query(&make_rect(0.0, 0.0, 20.0, 20.0)).len(),
+            20,
+            "All points should be queryable"
+        );
+    }
+
+    #[test]
+    fn get_item() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let point = point![20.0, 20.0];
+        qt.insert(&point);
+
+        assert_eq!(
+            qt.get(&point),
+            Some(point),
+            "Should find the inserted point"
+        );
+        assert!(
+            qt.get(&point![30.0, 30.0]).is_none(),
+            "Should not find a point that was not inserted"
+        );
+    }
+
+    #[test]
+    fn replace_item() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Record {
+            position: P2,
+            payload: u32,
+        }
+
+        impl Point for Record {
+            fn point(&self) -> P2 {
+                self.position
+            }
+        }
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let old = Record {
+            position: point![20.0, 20.0],
+            payload: 1,
+        };
+        qt.insert_many(&[
+            old.clone(),
+            Record {
+                position: point![70.0, 70.0],
+                payload: 2,
+            },
+        ]);
+
+        let new = Record {
+            position: old.position,
+            payload: 3,
+        };
+        assert_eq!(
+            qt.replace(&old.position, new.clone()),
+            Some(old.clone()),
+            "Should return the replaced item"
+        );
+        assert_eq!(qt.count(), 2, "Count should be unchanged");
+        assert_eq!(
+            qt.get(&old.position),
+            Some(new.clone()),
+            "Should find the new item"
+        );
+
+        let moved = Record {
+            position: point![30.0, 30.0],
+            payload: 4,
+        };
+        assert!(
+            qt.replace(&old.position, moved).is_none(),
+            "Should not replace with an item at a different position"
+        );
+        assert_eq!(
+            qt.get(&old.position),
+            Some(new),
+            "Item should be unchanged after a rejected replace"
+        );
+        let missing = Record {
+            position: point![50.0, 50.0],
+            payload: 5,
+        };
+        assert!(
+            qt.replace(&missing.position, missing.clone()).is_none(),
+            "Should not replace when no item exists at the position"
+        );
+    }
+
+    #[test]
+    fn query_rectangular() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let results = qt.query(&make_rect(10.0, 10.0, 50.0, 50.0));
+        assert!(results.is_empty(), "Should be empty for an empty tree");
+
+        let item = point![25.0, 25.0];
+        qt.insert(&item);
+        let results = qt.query(&make_rect(20.0, 20.0, 30.0, 30.0));
+        assert_eq!(results.len(), 1, "Should find one item in the range");
+        assert_eq!(
+            results[0].point(),
+            item.point(),
+            "The point should match the inserted item"
+        );
+
+        let results = qt.query(&make_rect(70.0, 70.0, 80.0, 80.0));
+        assert!(
+            results.is_empty(),
+            "Should not find any items outside the range"
+        );
+    }
+
+    #[test]
+    fn query_sorted_xy_independent_of_structure() {
+        let points = vec![
+            point![30.0, 10.0],
+            point![10.0, 90.0],
+            point![10.0, 20.0],
+            point![75.0, 75.0],
+            point![30.0, 5.0],
+            point![55.0, 40.0],
+        ];
+        let mut deep = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        for point in points.iter().rev() {
+            deep.insert(point);
+        }
+        let mut flat = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 16);
+        flat.insert_many(&points);
+
+        let area = make_rect(0.0, 0.0, 80.0, 80.0);
+        let expected = vec![
+            point![10.0, 20.0],
+            point![30.0, 5.0],
+            point![30.0, 10.0],
+            point![55.0, 40.0],
+            point![75.0, 75.0],
+        ];
+        assert_eq!(
+            deep.query_sorted_xy(&area),
+            expected,
+            "Results should be sorted by x, then y"
+        );
+        assert_eq!(
+            flat.query_sorted_xy(&area),
+            expected,
+            "Structurally different trees should produce identical output"
+        );
+    }
+
+    #[test]
+    fn query_ref_rectangular() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let item = point![25.0, 25.0];
+        qt.insert(&item);
+        let results = qt.query_ref(&make_rect(20.0, 20.0, 30.0, 30.0));
+        assert_eq!(results.len(), 1, "Should find one item in the range");
+        assert_eq!(
+            results[0].point(),
+            item.point(),
+            "The point should match the inserted item"
+        );
+    }
+
+    #[test]
+    fn query_rectangular_internal_nodes_multiple_items() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let item1 = point![25.0, 25.0];
+        let item2 = point![75.0, 75.0];
+        qt.insert(&item1);
+        qt.insert(&item2);
+
+        let results = qt.query(&make_rect(20.0, 20.0, 80.0, 80.0));
+        assert_eq!(results.len(), 2, "Should find both items in the range");
+
+        let results = qt.query(&make_rect(70.0, 70.0, 80.0, 80.0));
+        assert_eq!(results.len(), 1, "Should find one item in the range");
+        assert_eq!(
+            results[0].point(),
+            item2.point(),
+            "The point should match the second inserted item"
+        );
+
+        let results = qt.query(&make_rect(200.0, 200.0, 300.0, 300.0));
+        assert!(
+            results.is_empty(),
+            "Should not find any items outside the range"
+        );
+    }
+
+    #[test]
+    fn query_rectangular_boundary_edge_overlap() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        let edge_point = point![100.0, 50.0]; // Exactly on the boundary edge
+        qt.insert(&edge_point);
+        let query_shape = make_rect(95.0, 45.0, 105.0, 55.0);
+        let results = qt.query(&query_shape);
+        assert_eq!(results.len(), 1, "Should find the edge point.");
+    }
+
+    #[test]
+    fn query_circular_empty_tree() {
+        let mut qt = QuadTree::<P2>::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let circle = make_circle(50.0, 50.0, 10.0);
+        let results = qt.query(&circle);
+        assert!(results.is_empty(), "Should be empty for an empty tree");
+
+        let item = point![25.0, 25.0];
+        qt.insert(&item);
+        let circle = make_circle(20.0, 20.0, 10.0);
+        let results = qt.query(&circle);
+        assert_eq!(results.len(), 1, "Should find one item within the circle");
+        assert_eq!(
+            results[0].point(),
+            item.point(),
+            "The point should match the inserted item"
+        );
+
+        let circle = make_circle(80.0, 80.0, 5.0);
+        let results = qt.query(&circle);
+        assert!(
+            results.is_empty(),
+            "Should not find any items outside the circle"
+        );
+    }
+
+    #[test]
+    fn query_circular_internal_nodes_multiple_items() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let item1 = point![35.0, 35.0];
+        let item2 = point![65.0, 65.0];
+        qt.insert(&item1);
+        qt.insert(&item2);
+
+        let circle = make_circle(50.0, 50.0, 30.0);
+        let results = qt.query(&circle);
+        assert_eq!(results.len(), 2, "Should find both items within the circle");
+
+        let circle = make_circle(70.0, 70.0, 10.0);
+        let results = qt.query(&circle);
+        assert_eq!(results.len(), 1, "Should find one item within the circle");
+        assert_eq!(
+            results[0].point(),
+            item2.point(),
+            "The point should match the second inserted item"
+        );
+
+        let circle = make_circle(200.0, 200.0, 50.0);
+        let results = qt.query(&circle);
+        assert!(
+            results.is_empty(),
+            "Should not find any items outside the circle"
+        );
+    }
+
+    #[test]
+    fn query_half_plane() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![
+            point![10.0, 10.0],
+            point![90.0, 10.0],
+            point![10.0, 90.0],
+            point![90.0, 90.0],
+            point![40.0, 60.0],
+        ];
+        qt.insert_many(&points);
+
+        // Everything on or above the diagonal from (0, 0) to (100, 100)
+        let half_plane = HalfPlane::new(point![0.0, 0.0], vector![-1.0, 1.0]);
+        let results = qt.query(&half_plane);
+        assert_eq!(
+            results.len(),
+            4,
+            "Should find points on the positive side and the edge"
+        );
+        assert!(
+            !results.contains(&points[1]),
+            "Should not find point (90, 10) on the negative side"
+        );
+    }
+
+    #[test]
+    fn query_filter_exclude_point() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let items = vec![point![40.0, 40.0], point![50.0, 50.0], point![60.0, 60.0]];
+        qt.insert_many(&items);
+
+        let area = Circle::new(items[1], 20.0);
+        let results = qt.query_filter(&area, |p| p.point() != area.center());
+        assert!(
+            !results.contains(&items[1]),
+            "Should not find the excluded point"
+        );
+        assert_eq!(results.len(), 2, "Should find two points within the circle");
+    }
+
+    #[test]
+    fn query_ref_filter_exclude_point() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let items = vec![point![40.0, 40.0], point![50.0, 50.0], point![60.0, 60.0]];
+        qt.insert_many(&items);
+
+        let area = Circle::new(items[1], 20.0);
+        let results = qt.query_ref_filter(&area, |p| p.point() != area.center());
+        assert!(
+            !results.contains(&&items[1]),
+            "Should not find the excluded point"
+        );
+        assert_eq!(results.len(), 2, "Should find two points within the circle");
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn nearest_batch_matches_serial() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.insert_many(&scattered_points(500));
+        let queries: Vec<P2> = scattered_points(300)
+            .iter()
+            .map(|p| point![p.y * 1.2 - 10.0, p.x * 1.2 - 10.0])
+            .collect();
+
+        let serial: Vec<_> = queries.iter().map(|q| qt.nearest(q)).collect();
+        assert_eq!(qt.nearest_batch(&queries), serial);
+        assert!(QuadTree::<P2>::new(qt.boundary(), 4)
+            .nearest_batch(&queries)
+            .iter()
+            .all(Option::is_none));
+    }
+
+    #[test]
+    fn nearest_item() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        assert!(
+            qt.nearest(&point![50.0, 50.0]).is_none(),
+            "Should find nothing in an empty tree"
+        );
+
+        let points = vec![
+            point![10.0, 10.0],
+            point![46.0, 48.0],
+            point![55.0, 60.0],
+            point![90.0, 90.0],
+        ];
+        qt.insert_many(&points);
+        assert_eq!(
+            qt.nearest(&point![52.0, 52.0]),
+            Some(points[1]),
+            "Should find the nearest point across a quadrant boundary"
+        );
+        assert_eq!(
+            qt.nearest(&point![200.0, 200.0]),
+            Some(points[3]),
+            "Should find the nearest point from outside the boundary"
+        );
+    }
+
+    #[test]
+    fn pop_nearest_drains_in_order() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let points = (0..20)
+            .map(|i| point![(i * 37 % 100) as f64, (i * 61 % 100) as f64])
+            .collect::<Vec<_>>();
+        qt.insert_many(&points);
+
+        let origin = point![30.0, 70.0];
+        let mut last_distance = 0.0;
+        for remaining in (0..points.len()).rev() {
+            let item = qt.pop_nearest(&origin).expect("Should pop an item");
+            let distance = na::distance(&item, &origin);
+            assert!(
+                distance >= last_distance,
+                "Items should be popped in non-decreasing distance"
+            );
+            assert_eq!(qt.count(), remaining, "Count should decrement");
+            last_distance = distance;
+        }
+
+        assert!(
+            qt.pop_nearest(&origin).is_none(),
+            "Should pop nothing from an empty tree"
+        );
+        assert!(
+            matches!(qt.root, Node::Empty { .. }),
+            "Drained tree should collapse to an empty root"
+        );
+    }
+
+    #[test]
+    fn delete_rect() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![point![10.0, 10.0], point![30.0, 10.0], point![10.0, 30.0]];
+        qt.insert_many(&points);
+
+        let deletion_shape = make_rect(5.0, 5.0, 35.0, 15.0);
+        let deleted = qt.delete(&deletion_shape);
+        assert_eq!(deleted, 2, "Two items were deleted");
+        assert_eq!(qt.count(), 1, "One item remains in tree");
+        assert!(
+            qt.get(&points[0]).is_none(),
+            "Point at (10.0, 10.0) should have been deleted"
+        );
+        assert!(
+            qt.get(&points[1]).is_none(),
+            "Point at (30.0, 10.0) should have been deleted"
+        );
+        assert!(
+            qt.get(&points[2]).is_some(),
+            "Point at (10.0, 30.0) should still exist"
+        );
+    }
+
+    #[test]
+    fn delete_rect_bounds() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![point![10.0, 10.0], point![20.0, 20.0], point![30.0, 30.0]];
+        for point in &points {
+            qt.insert(point);
+        }
+        assert_eq!(qt.count(), 3, "Three items should be in the tree");
+
+        let deletion_shape = make_rect(15.0, 15.0, 25.0, 25.0);
+        let deleted = qt.delete(&deletion_shape);
+        assert_eq!(deleted, 1, "One item was deleted");
+        assert_eq!(qt.count(), 2, "Two items remain in tree");
+        assert!(
+            qt.get(&points[0]).is_some(),
+            "Point at (10.0, 10.0) should still exist"
+        );
+        assert!(
+            qt.get(&points[1]).is_none(),
+            "Point at (20.0, 20.0) should have been deleted"
+        );
+        assert!(
+            qt.get(&points[2]).is_some(),
+            "Point at (30.0, 30.0) should still exist"
+        );
+    }
+
+    #[test]
+    fn delete_rect_multi_level() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        // Points to cause subdivisions
+        let points = [
+            point![10.0, 10.0],
+            point![90.0, 90.0],
+            point![10.0, 90.0],
+            point![90.0, 10.0],
+            point![50.0, 50.0],
+            point![30.0, 30.0],
+        ];
+        for p in &points {
+            qt.insert(p);
+        }
+        let deletion_shape = make_rect(25.0, 25.0, 75.0, 75.0);
+        qt.delete(&deletion_shape);
+        let results = qt.query(&make_rect(0.0, 0.0, 100.0, 100.0));
+        assert_eq!(
+            results.len(),
+            4,
+            "Should have 4 points left after partial deletion."
+        );
+    }
+
+    #[test]
+    fn any_short_circuits() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![
+            point![10.0, 10.0],
+            point![20.0, 20.0],
+            point![30.0, 30.0],
+            point![80.0, 80.0],
+        ];
+        qt.insert_many(&points);
+
+        let calls = core::cell::Cell::new(0);
+        let found = qt.any(&make_rect(0.0, 0.0, 50.0, 50.0), |_| {
+            calls.set(calls.get() + 1);
+            assert_eq!(calls.get(), 1, "Predicate should not be called again");
+            true
+        });
+        assert!(found, "Should find a matching item");
+
+        assert!(
+            !qt.any(&make_rect(0.0, 0.0, 50.0, 50.0), |p| p.x > 50.0),
+            "Should not find an item that fails the predicate"
+        );
+        assert!(
+            !qt.any(&make_rect(60.0, 0.0, 100.0, 50.0), |_| true),
+            "Should not find an item outside the shape"
+        );
+    }
+
+    #[test]
+    fn density_grid_counts() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let points = vec![
+            point![10.0, 10.0],
+            point![20.0, 40.0],
+            point![60.0, 10.0],
+            point![70.0, 20.0],
+            point![90.0, 45.0],
+            point![50.0, 50.0],   // On a shared edge, bucketed into the last cell
+            point![100.0, 100.0], // On the far corner, bucketed into the last cell
+            point![10.0, 80.0],
+        ];
+        qt.insert_many(&points);
+
+        let grid = qt.density_grid(make_rect(0.0, 0.0, 100.0, 100.0), 2, 2);
+        assert_eq!(grid, vec![2, 3, 1, 2], "Buckets should match the scatter");
+
+        let grid = qt.density_grid(make_rect(0.0, 0.0, 100.0, 50.0), 4, 1);
+        assert_eq!(
+            grid,
+            vec![2, 0, 3, 1],
+            "Only points within the region should be counted"
+        );
+
+        assert!(
+            qt.density_grid(make_rect(0.0, 0.0, 100.0, 100.0), 0, 4)
+                .is_empty(),
+            "A grid without columns should be empty"
+        );
+    }
+
+    #[test]
+    fn query_many_overlapping_shapes() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![
+            point![10.0, 10.0],
+            point![20.0, 20.0],
+            point![30.0, 30.0],
+            point![80.0, 80.0],
+        ];
+        qt.insert_many(&points);
+
+        let shapes = [
+            make_rect(5.0, 5.0, 25.0, 25.0),
+            make_rect(15.0, 15.0, 35.0, 35.0),
+        ];
+        let results = qt.query_many(&shapes);
+        assert_eq!(
+            results.len(),
+            3,
+            "Point (20, 20) inside both shapes should only be returned once"
+        );
+        assert!(
+            !results.contains(&points[3]),
+            "Point (80, 80) is outside all shapes"
+        );
+
+        assert!(
+            qt.query_many::<Rect>(&[]).is_empty(),
+            "Should find nothing with no shapes"
+        );
+    }
+
+    #[test]
+    fn delete_many_overlapping_shapes() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![
+            point![10.0, 10.0],
+            point![20.0, 20.0],
+            point![30.0, 30.0],
+            point![80.0, 80.0],
+        ];
+        qt.insert_many(&points);
+
+        let shapes = [make_circle(15.0, 15.0, 10.0), make_circle(25.0, 25.0, 10.0)];
+        let deleted = qt.delete_many(&shapes);
+        assert_eq!(
+            deleted, 3,
+            "Point (20, 20) inside both shapes should only be counted once"
+        );
+        assert_eq!(qt.count(), 1, "One item remains in tree");
+        assert!(
+            qt.get(&points[3]).is_some(),
+            "Point at (80.0, 80.0) should still exist"
+        );
+    }
+
+    #[test]
+    fn delete_filter_exclude_point() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![point![15.0, 15.0], point![20.0, 20.0], point![25.0, 25.0]];
+        qt.insert_many(&points);
+
+        let area = make_rect(10.0, 10.0, 30.0, 30.0);
+        let deleted = qt.delete_filter(&area, |p| p.point() != area.center());
+        assert_eq!(deleted, 2, "Two items were deleted");
+        assert_eq!(qt.count(), 1, "One item remains in tree");
+        assert!(
+            qt.get(&points[0]).is_none(),
+            "Point at (15.0, 15.0) should have been deleted"
+        );
+        assert!(
+            qt.get(&points[1]).is_some(),
+            "Point at (20.0, 20.0) should still exist"
+        );
+        assert!(
+            qt.get(&points[2]).is_none(),
+            "Point at (25.0, 25.0) should have been deleted"
+        );
+    }
+
+    #[test]
+    fn test_pop_function() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![
+            point![10.0, 10.0],
+            point![20.0, 20.0],
+            point![30.0, 30.0],
+            point![40.0, 40.0],
+        ];
+
+        qt.insert_many(&points);
+
+        assert_eq!(qt.count(), 4, "All four points were inserted");
+
+        let results = qt.pop(&make_rect(15.0, 15.0, 35.0, 35.0));
+        assert_eq!(
+            results.len(),
+            2,
+            "Should pop two points that intersect with the shape"
+        );
+        assert!(
+            results.contains(&points[1]),
+            "Should contain point (20, 20)"
+        );
+        assert!(
+            results.contains(&points[2]),
+            "Should contain point (30, 30)"
+        );
+
+        let remaining_points = qt.query(&make_rect(0.0, 0.0, 100.0, 100.0));
+        assert_eq!(qt.count(), 2, "Two points should remain in the quadtree");
+        assert!(
+            remaining_points.contains(&points[0]),
+            "Should still contain point (10, 10)"
+        );
+        assert!(
+            remaining_points.contains(&points[3]),
+            "Should still contain point (40, 40)"
+        );
+    }
+
+    #[test]
+    fn pop_filter_exclude_point() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![point![15.0, 15.0], point![20.0, 20.0], point![25.0, 25.0]];
+        qt.insert_many(&points);
+
+        let area = make_rect(10.0, 10.0, 30.0, 30.0);
+        let results = qt.pop_filter(&area, |p| p.point() != area.center());
+        assert_eq!(results.len(), 2, "Two items were popped");
+        assert_eq!(qt.count(), 1, "One item remains in tree");
+        assert!(
+            results.contains(&points[2]),
+            "Point at (25.0, 25.0) should have been popped"
+        );
+        assert!(
+            !results.contains(&points[1]),
+            "Point at (20.0, 20.0) should not have been popped"
+        );
+        assert!(
+            qt.get(&points[1]).is_some(),
+            "Point at (20.0, 20.0) should still exist"
+        );
+    }
+
+    #[test]
+    fn take_items() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![
+            point![10.0, 10.0],
+            point![20.0, 20.0],
+            point![30.0, 30.0],
+            point![40.0, 40.0],
+        ];
+        qt.insert_many(&points);
+
+        let results = qt.take(&make_rect(15.0, 15.0, 35.0, 35.0));
+        assert_eq!(results.len(), 2, "Should take two points within the shape");
+        assert!(
+            results.contains(&points[1]),
+            "Should contain point (20, 20)"
+        );
+        assert!(
+            results.contains(&points[2]),
+            "Should contain point (30, 30)"
+        );
+        assert_eq!(qt.count(), 2, "Two points should remain in the quadtree");
+        assert!(qt.get(&points[0]).is_some(), "Point (10, 10) should remain");
+        assert!(qt.get(&points[3]).is_some(), "Point (40, 40) should remain");
+    }
+
+    #[test]
+    fn take_filter_exclude_point() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![point![15.0, 15.0], point![20.0, 20.0], point![25.0, 25.0]];
+        qt.insert_many(&points);
+
+        let area = make_rect(10.0, 10.0, 30.0, 30.0);
+        let results = qt.take_filter(&area, |p| p.point() != area.center());
+        assert_eq!(results.len(), 2, "Two items were taken");
+        assert_eq!(qt.count(), 1, "One item remains in tree");
+        assert!(
+            !results.contains(&points[1]),
+            "Point at (20.0, 20.0) should not have been taken"
+        );
+        assert!(
+            qt.get(&points[1]).is_some(),
+            "Point at (20.0, 20.0) should still exist"
+        );
+    }
+
+    #[test]
+    fn node_boundaries_depths() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        assert_eq!(
+            qt.node_boundaries(),
+            vec![(make_rect(0.0, 0.0, 100.0, 100.0), 0)],
+            "Empty tree should only have the root boundary"
+        );
+
+        qt.insert_many(&[point![10.0, 10.0], point![20.0, 20.0]]);
+        let boundaries = qt.node_boundaries();
+        assert!(
+            boundaries.contains(&(make_rect(0.0, 0.0, 100.0, 100.0), 0)),
+            "Root boundary should be present at depth 0"
+        );
+        assert!(
+            boundaries.contains(&(make_rect(0.0, 0.0, 50.0, 50.0), 1)),
+            "Subdivided quadrant should be present at depth 1"
+        );
+        assert!(
+            boundaries.contains(&(make_rect(0.0, 0.0, 25.0, 25.0), 2)),
+            "Leaf containing both points should be subdivided at depth 2"
+        );
+        assert_eq!(
+            boundaries.len(),
+            13,
+            "Root plus three levels of four children should be present"
+        );
+    }
+
+    #[test]
+    fn memory_usage_scales() {
+        let mut small = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        let mut large = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        let empty_usage = small.memory_usage();
+
+        small.insert_many(&[point![10.0, 10.0], point![20.0, 20.0]]);
+        let points = (0..100)
+            .map(|i| point![i as f64, (i * 7 % 100) as f64])
+            .collect::<Vec<_>>();
+        large.insert_many(&points);
+
+        assert!(
+            small.memory_usage() > empty_usage,
+            "Storing items should use more memory than an empty tree"
+        );
+        assert!(
+            large.memory_usage() > small.memory_usage(),
+            "A tree with more items should use more memory"
+        );
+    }
+
+    #[test]
+    fn shrink_to_fit_after_pop() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 64);
+        let points = (0..64)
+            .map(|i| point![(i % 8) as f64 * 10.0, (i / 8) as f64 * 10.0])
+            .collect::<Vec<_>>();
+        qt.insert_many(&points);
+
+        let popped = qt.pop(&make_rect(0.0, 0.0, 100.0, 55.0));
+        assert_eq!(popped.len(), 48, "Should pop most of the points");
+        let usage_before = qt.memory_usage();
+
+        qt.shrink_to_fit();
+        assert!(
+            qt.memory_usage() < usage_before,
+            "Shrinking should release the spare capacity"
+        );
+        assert_eq!(qt.count(), 16, "Count should be unchanged by shrinking");
+        assert_eq!(
+            qt.query(&qt.boundary()).len(),
+            16,
+            "Remaining points should still be queryable"
+        );
+    }
+
+    #[test]
+    fn leaves_cover_all_items() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let points = (0..30)
+            .map(|i| point![(i * 37 % 100) as f64, (i * 61 % 100) as f64])
+            .collect::<Vec<_>>();
+        qt.insert_many(&points);
+        qt.delete(&make_rect(0.0, 0.0, 30.0, 30.0));
+
+        let leaves = qt.leaves().collect::<Vec<_>>();
+        assert!(
+            leaves.iter().all(|(_, items)| !items.is_empty()),
+            "Empty leaves should be skipped"
+        );
+        assert!(
+            leaves
+                .iter()
+                .all(|(boundary, items)| items.iter().all(|p| boundary.contains(p))),
+            "Items should lie within their leaf's boundary"
+        );
+
+        let mut flattened = leaves
+            .iter()
+            .flat_map(|(_, items)| items.iter().copied())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flattened.len(),
+            qt.count(),
+            "Leaves should cover every item exactly once"
+        );
+        flattened.sort_by(cmp_xy);
+        assert_eq!(
+            flattened,
+            qt.query_sorted_xy(&qt.boundary()),
+            "Leaves should contain exactly the stored items"
+        );
+    }
+
+    #[test]
+    fn map_items() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Labeled {
+            position: P2,
+            label: usize,
+        }
+
+        impl Point for Labeled {
+            fn point(&self) -> P2 {
+                self.position
+            }
+        }
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![point![10.0, 10.0], point![20.0, 20.0], point![80.0, 80.0]];
+        qt.insert_many(&points);
+
+        let mapped = qt.map(|p| Labeled {
+            position: *p,
+            label: (p.x + p.y) as usize,
+        });
+        assert_eq!(mapped.count(), qt.count(), "Count should be preserved");
+        assert_eq!(
+            mapped.node_boundaries(),
+            qt.node_boundaries(),
+            "Structure should be preserved"
+        );
+        assert_eq!(mapped.validate(), Ok(()), "Mapped tree should be valid");
+
+        let results = mapped.query(&make_rect(0.0, 0.0, 50.0, 50.0));
+        assert_eq!(results.len(), 2, "Should find two mapped items");
+        assert!(
+            results.contains(&Labeled {
+                position: points[1],
+                label: 40,
+            }),
+            "Mapped item should carry its computed label"
+        );
+    }
+
+    #[test]
+    fn validate_invariants() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        assert_eq!(qt.validate(), Ok(()), "Empty tree should be valid");
+
+        let points = (0..50)
+            .map(|i| point![(i * 37 % 100) as f64, (i * 61 % 100) as f64])
+            .collect::<Vec<_>>();
+        qt.insert_many(&points);
+        qt.delete(&make_circle(50.0, 50.0, 20.0));
+        assert_eq!(
+            qt.validate(),
+            Ok(()),
+            "Tree should be valid after mutations"
+        );
+
+        qt.count += 1;
+        assert!(
+            qt.validate().is_err_and(|e| e.starts_with("count")),
+            "Mismatched count should be reported"
+        );
+        qt.count -= 1;
+
+        if let Node::Internal { children, .. } = &mut qt.root {
+            children.swap(0, 3);
+        }
+        assert!(
+            qt.validate().is_err_and(|e| e.starts_with("child")),
+            "Misplaced child should be reported"
+        );
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        qt.root = Node::External {
+            boundary: qt.boundary(),
+            data: vec![point![10.0, 10.0], point![150.0, 10.0]],
+        };
+        qt.count = 2;
+        assert!(
+            qt.validate().is_err_and(|e| e.starts_with("item")),
+            "Item outside of its leaf should be reported"
+        );
+
+        let mut zero = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 0);
+        zero.insert_many(&points);
+        assert_eq!(
+            zero.validate(),
+            Ok(()),
+            "Leaves of a zero capacity tree should hold one item"
+        );
+
+        qt.root = Node::External {
+            boundary: qt.boundary(),
+            data: vec![point![10.0, 10.0]; 3],
+        };
+        qt.count = 3;
+        assert!(
+            qt.validate().is_err_and(|e| e.starts_with("leaf")),
+            "Leaf over capacity should be reported"
+        );
+    }
+
+    #[test]
+    fn precise_floating_point_handling() {
+        let mut qt = QuadTree::new(make_rect(0.00001, 0.00001, 99.99999, 99.99999), 2);
+        let point = point![0.0001, 0.0001];
+        assert!(
+            qt.insert(&point),
+            "Point near the boundary should be inserted."
+        );
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn svg_output() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![point![10.0, 10.0], point![20.0, 20.0], point![80.0, 30.0]];
+        qt.insert_many(&points);
+
+        let svg = qt.to_svg();
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">"#),
+            "View box should match the boundary"
+        );
+        assert_eq!(
+            svg.matches("<circle").count(),
+            qt.count(),
+            "Should render one circle per item"
+        );
+        assert_eq!(
+            svg.matches("<rect").count(),
+            qt.node_boundaries().len(),
+            "Should render one rect per node"
+        );
+    }
+
+    #[cfg(feature = "geojson")]
+    #[test]
+    fn geojson_round_trip() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![
+            point![10.0, 10.0],
+            point![20.5, 20.0],
+            point![80.0, 30.25],
+            point![55.0, 90.0],
+        ];
+        qt.insert_many(&points);
+
+        let geojson = qt.to_geojson();
+        let parsed = QuadTree::from_geojson(qt.boundary(), 1, &geojson)
+            .expect("GeoJSON should parse successfully");
+        assert_eq!(parsed.count(), points.len(), "All points should survive");
+        for point in &points {
+            assert!(
+                parsed.get(point).is_some(),
+                "Point {point} should survive the round trip"
+            );
+        }
+
+        assert!(
+            QuadTree::from_geojson(make_rect(0.0, 0.0, 50.0, 50.0), 1, &geojson).is_err(),
+            "Points outside the boundary should be rejected"
+        );
+        let overflow = r#"{"type":"FeatureCollection","features":[{"type":"Feature","geometry":{"type":"Point","coordinates":[1e999,0.0]}}]}"#;
+        let err = QuadTree::from_geojson(qt.boundary(), 1, overflow)
+            .expect_err("Non-finite coordinates should be rejected");
+        assert!(
+            !format!("{err}").contains("outside of the boundary"),
+            "Non-finite coordinates should not be reported as out of bounds: {err}"
+        );
+        assert!(
+            QuadTree::from_geojson(qt.boundary(), 1, r#"{"type":"Feature"}"#).is_err(),
+            "Documents that are not a FeatureCollection should be rejected"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_quadtree_serialization() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![point![10.0, 10.0], point![20.0, 20.0], point![30.0, 30.0]];
+        qt.insert_many(&points);
+
+        let serialized = serde_json::to_string(&qt).expect("Failed to serialize QuadTree");
+        let expected_json = r#"[[10.0,10.0],[20.0,20.0],[30.0,30.0]]"#;
+
+        assert_eq!(
+            serialized, expected_json,
+            "Serialized QuadTree does not match expected JSON output"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_region_matches_query() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.insert_many(&scattered_points(200));
+        let region = make_rect(20.0, 30.0, 60.0, 70.0);
+
+        let serialized = serde_json::to_string(&qt.serialize_region(&region))
+            .expect("Failed to serialize region");
+        let expected =
+            serde_json::to_string(&qt.query(&region)).expect("Failed to serialize items");
+        assert!(!qt.query(&region).is_empty());
+        assert_eq!(
+            serialized, expected,
+            "Serialized region should match the queried items"
+        );
+
+        let serialized =
+            serde_json::to_string(&qt.serialize_region(&make_rect(200.0, 200.0, 300.0, 300.0)))
+                .expect("Failed to serialize region");
+        assert_eq!(
+            serialized, "[]",
+            "Region outside of the tree should serialize as an empty sequence"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn full_serialization_round_trip() {
+        let mut qt = QuadTree::new(make_rect(-50.0, 0.0, 100.0, 100.0), 2);
+        let points: Vec<P2> = (0..40)
+            .map(|i| point![(i * 37 % 150) as f64 - 49.5, (i * 61 % 100) as f64 + 0.25])
+            .collect();
+        qt.insert_many(&points);
+
+        let mut buffer = Vec::new();
+        qt.serialize_full(&mut serde_json::Serializer::new(&mut buffer))
+            .expect("Failed to serialize QuadTree");
+        let json = String::from_utf8(buffer).expect("Serialized QuadTree should be UTF-8");
+        assert!(
+            json.starts_with(r#"{"boundary":{"start":[-50.0,0.0],"end":[100.0,100.0]},"node_capacity":2,"items":["#),
+            "Serialized QuadTree should include its metadata"
+        );
+
+        let rebuilt: QuadTree<P2> =
+            QuadTree::deserialize_full(&mut serde_json::Deserializer::from_str(&json))
+                .expect("Failed to deserialize QuadTree");
+        assert_eq!(
+            rebuilt.boundary(),
+            qt.boundary(),
+            "Boundary should round-trip"
+        );
+        assert_eq!(
+            rebuilt.capacity.fixed_value(),
+            Some(2),
+            "Node capacity should round-trip"
+        );
+        assert_eq!(rebuilt.count(), qt.count());
+        assert_eq!(
+            rebuilt.depth(),
+            qt.depth(),
+            "Rebuilt tree should have the same shape"
+        );
+        let mut expected: Vec<P2> = qt.iter_positions().map(|(p, _)| p).collect();
+        let mut actual: Vec<P2> = rebuilt.iter_positions().map(|(p, _)| p).collect();
+        expected.sort_by(cmp_xy);
+        actual.sort_by(cmp_xy);
+        assert_eq!(actual, expected, "Items should round-trip");
+
+        let out_of_bounds = r#"{"boundary":{"start":[0.0,0.0],"end":[1.0,1.0]},"node_capacity":2,"items":[[5.0,5.0]]}"#;
+        assert!(
+            QuadTree::<P2>::deserialize_full(&mut serde_json::Deserializer::from_str(
+                out_of_bounds
+            ))
+            .is_err(),
+            "Items outside of the boundary should be rejected"
+        );
+
+        // Positions are scaled from the stored values, so large values overflow to infinity
+        #[derive(Clone, Debug, Deserialize)]
+        struct Scaled(P2);
+        impl Point for Scaled {
+            fn point(&self) -> P2 {
+                self.0 * 1e10
+            }
+        }
+        let non_finite = r#"{"boundary":{"start":[0.0,0.0],"end":[1.0,1.0]},"node_capacity":2,"items":[[1e300,0.0]]}"#;
+        let err = QuadTree::<Scaled>::deserialize_full(&mut serde_json::Deserializer::from_str(
+            non_finite,
+        ))
+        .expect_err("Items with a non-finite position should be rejected");
+        assert!(
+            format!("{err}").contains("non-finite"),
+            "Non-finite items should be reported as such, not as out of bounds: {err}"
+        );
+
+        let qt = QuadTree::<P2>::with_capacity_fn(make_rect(0.0, 0.0, 1.0, 1.0), |_| 4);
+        assert!(
+            qt.serialize_full(&mut serde_json::Serializer::new(Vec::new()))
+                .is_err(),
+            "Capacity functions should not serialize"
+        );
+    }
+
+    #[test]
+    fn get_or_insert_existing_and_new() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Record {
+            position: P2,
+            id: usize,
+        }
+
+        impl Point for Record {
+            fn point(&self) -> P2 {
+                self.position
+            }
+        }
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let first = Record {
+            position: point![30.0, 40.0],
+            id: 1,
+        };
+        assert_eq!(
+            qt.get_or_insert(&first),
+            first,
+            "Should return the new item"
+        );
+        assert_eq!(qt.count(), 1, "Item should be inserted");
+
+        let duplicate = Record {
+            position: point![30.0, 40.0],
+            id: 2,
+        };
+        assert_eq!(
+            qt.get_or_insert(&duplicate),
+            first,
+            "Should return the existing item at the same point"
+        );
+        assert_eq!(qt.count(), 1, "Existing point should not be inserted again");
+    }
+
+    #[test]
+    fn nearest_iter_yields_in_distance_order() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        let points = scattered_points(300);
+        qt.insert_many(&points);
+
+        let target = point![37.0, 61.0];
+        let mut expected = points.clone();
+        expected.sort_by(|a, b| {
+            na::distance_squared(a, &target).total_cmp(&na::distance_squared(b, &target))
+        });
+
+        let nearest: Vec<P2> = qt.nearest_iter(target).take(25).copied().collect();
+        assert_eq!(
+            nearest,
+            expected[..25],
+            "First items should be the 25 nearest"
+        );
+        assert_eq!(
+            qt.nearest_iter(target).next().copied(),
+            qt.nearest(&target),
+            "First item should match nearest"
+        );
+        assert_eq!(
+            qt.nearest_iter(target).count(),
+            points.len(),
+            "Every item should be yielded"
+        );
+    }
+
+    #[test]
+    fn total_bounds_of_scattered_points() {
+        let mut qt = QuadTree::new(make_rect(-500.0, -500.0, 500.0, 500.0), 4);
+        assert_eq!(qt.total_bounds(), None, "Empty tree should have no bounds");
+
+        let points = scattered_points(200);
+        qt.insert_many(&points);
+
+        let min_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min);
+        let min_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min);
+        let max_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max);
+        let max_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max);
+
+        let bounds = qt
+            .total_bounds()
+            .expect("Non-empty tree should have bounds");
+        assert_eq!(
+            bounds.start(),
+            point![min_x, min_y],
+            "Start should be the minimum corner"
+        );
+        assert_eq!(
+            bounds.end(),
+            point![max_x, max_y],
+            "End should be the maximum corner"
+        );
+
+        let mut corners = QuadTree::new(make_rect(-500.0, -500.0, 500.0, 500.0), 4);
+        corners.insert_many(&[point![-400.0, 300.0], point![250.0, -100.0]]);
+        assert_eq!(
+            corners.total_bounds(),
+            Some(make_rect(-400.0, -100.0, 250.0, 300.0)),
+            "Bounds should span just the two points"
+        );
+    }
+
+    #[test]
+    fn quadtree_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<QuadTree<P2>>();
+        assert_send_sync::<FixedQuadTree<P2, 4>>();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn concurrent_queries() {
+        let mut qt = QuadTree::with_capacity_fn(make_rect(0.0, 0.0, 100.0, 100.0), |_| 4);
+        let points = scattered_points(1000);
+        qt.insert_many(&points);
+
+        let regions = [
+            make_rect(0.0, 0.0, 50.0, 50.0),
+            make_rect(25.0, 25.0, 75.0, 75.0),
+            make_rect(50.0, 0.0, 100.0, 100.0),
+            make_rect(10.0, 60.0, 40.0, 90.0),
+        ];
+        let expected: Vec<usize> = regions.iter().map(|r| qt.query(r).len()).collect();
+
+        let qt = &qt;
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = regions
+                .iter()
+                .map(|region| scope.spawn(move || qt.query(region).len()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(
+            counts, expected,
+            "Concurrent queries should match sequential ones"
+        );
+    }
+
+    #[test]
+    fn snapshot_is_unaffected_by_mutation() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4).into_shared();
+        let points = scattered_points(200);
+        qt.insert_many(&points);
+
+        let snapshot = qt.snapshot();
+        let structure = snapshot.node_boundaries();
+        if let (Node::Internal { children: a, .. }, Node::Internal { children: b, .. }) =
+            (&qt.root, &snapshot.root)
+        {
+            assert!(
+                a.iter().zip(b).all(|(a, b)| Arc::ptr_eq(a, b)),
+                "Snapshot should share nodes with the original"
+            );
+        }
+
+        let region = make_rect(0.0, 0.0, 50.0, 50.0);
+        let removed = qt.delete(&region);
+        assert!(removed > 0, "Should delete items from the original");
+        qt.insert(&point![75.0, 75.0]);
+
+        assert_eq!(
+            snapshot.count(),
+            points.len(),
+            "Snapshot count should be unchanged"
+        );
+        assert_eq!(
+            snapshot.query(&region).len(),
+            removed,
+            "Snapshot should keep deleted items"
+        );
+        assert_eq!(
+            snapshot.node_boundaries(),
+            structure,
+            "Snapshot structure should be unchanged"
+        );
+        assert_eq!(snapshot.validate(), Ok(()));
+        assert_eq!(qt.count(), points.len() - removed + 1);
+    }
+
+    #[test]
+    fn clone_copies_unshared_nodes() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        let points = scattered_points(200);
+        qt.insert_many(&points);
+
+        let mut clone = qt.clone();
+        if let (Node::Internal { children: a, .. }, Node::Internal { children: b, .. }) =
+            (&qt.root, &clone.root)
+        {
+            assert!(
+                a.iter().zip(b).all(|(a, b)| !Arc::ptr_eq(a, b)),
+                "Clone of a unique tree should own copies of the nodes"
+            );
+        }
+
+        let region = make_rect(0.0, 0.0, 50.0, 50.0);
+        let removed = clone.delete(&region);
+        assert!(removed > 0, "Should delete items from the clone");
+        assert_eq!(
+            qt.query(&region).len(),
+            removed,
+            "Original should keep the items deleted from the clone"
+        );
+        assert_eq!(
+            qt.count(),
+            points.len(),
+            "Original count should be unchanged"
+        );
+        assert_eq!(qt.validate(), Ok(()));
+    }
+
+    #[test]
+    fn mutating_a_clone_keeps_untouched_children_shared() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4).into_shared();
+        qt.insert_many(&scattered_points(200));
+        let region = make_rect(5.0, 5.0, 40.0, 40.0);
+
+        let assert_shared = |a: &SharedQuadTree<P2>, b: &SharedQuadTree<P2>, op: &str| {
+            let (Node::Internal { children: a, .. }, Node::Internal { children: b, .. }) =
+                (&a.root, &b.root)
+            else {
+                panic!("Both roots should be subdivided");
+            };
+            for (a, b) in a.iter().zip(b) {
+                assert_eq!(
+                    Arc::ptr_eq(a, b),
+                    !region.intersects_rect(&b.boundary()),
+                    "{op} should only copy the children it touches"
+                );
+            }
+        };
+
+        let mut clone = qt.clone();
+        assert!(clone.delete(&region) > 0);
+        assert_shared(&clone, &qt, "delete");
+
+        let mut clone = qt.clone();
+        assert!(clone.delete_many(&[region]) > 0);
+        assert_shared(&clone, &qt, "delete_many");
+
+        let mut clone = qt.clone();
+        assert!(!clone.pop(&region).is_empty());
+        assert_shared(&clone, &qt, "pop");
+
+        let mut clone = qt.clone();
+        clone.shrink_to_fit();
+        if let (Node::Internal { children: a, .. }, Node::Internal { children: b, .. }) =
+            (&clone.root, &qt.root)
+        {
+            assert!(
+                a.iter().zip(b).all(|(a, b)| Arc::ptr_eq(a, b)),
+                "shrink_to_fit should not copy shared children"
+            );
+        }
+    }
+
+    #[test]
+    fn query_limit_caps_results() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.insert_many(&scattered_points(500));
+
+        let region = make_circle(50.0, 50.0, 30.0);
+        let all = qt.query(&region);
+        for limit in [0, 1, 7, 50] {
+            let limited = qt.query_limit(&region, limit);
+            assert_eq!(limited.len(), limit, "Should return exactly `limit` items");
+            assert!(
+                limited.iter().all(|p| all.contains(p)),
+                "Every limited result should match the shape"
+            );
+        }
+
+        assert_eq!(
+            qt.query_limit(&region, all.len() + 10),
+            all,
+            "A limit above the match count should behave like query"
+        );
+    }
+
+    #[test]
+    fn quadrant_counts_in_region() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        qt.insert_many(&[
+            point![15.0, 15.0],
+            point![25.0, 25.0],
+            point![45.0, 20.0],
+            point![55.0, 10.0],
+            point![15.0, 45.0],
+            point![25.0, 55.0],
+            point![35.0, 45.0],
+            point![70.0, 70.0],
+            point![90.0, 90.0],
+        ]);
+
+        assert_eq!(
+            qt.quadrant_counts(make_rect(10.0, 10.0, 50.0, 50.0)),
+            [2, 1, 1, 1],
+            "Should count items in each quarter and ignore items outside the region"
+        );
+        assert_eq!(
+            qt.quadrant_counts(make_rect(0.0, 0.0, 100.0, 100.0)),
+            [5, 1, 1, 2],
+            "Should count every item when the region is the boundary"
+        );
+    }
+
+    #[test]
+    fn raycast_returns_first_hit() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        qt.insert_many(&[
+            point![80.0, 51.0],
+            point![40.0, 49.5],
+            point![20.0, 55.0],
+            point![5.0, 50.0],
+            point![60.0, 50.0],
+        ]);
+
+        assert_eq!(
+            qt.raycast(point![10.0, 50.0], vector![1.0, 0.0], 1.0),
+            Some(point![40.0, 49.5]),
+            "Should hit the nearest point along the ray within tolerance"
+        );
+        assert_eq!(
+            qt.raycast(point![90.0, 50.0], vector![-1.0, 0.0], 2.0),
+            Some(point![80.0, 51.0]),
+            "Should hit the nearest point in the reverse direction"
+        );
+        assert_eq!(
+            qt.raycast(point![10.0, 50.0], vector![0.0, 1.0], 1.0),
+            None,
+            "Should miss when no point is within tolerance"
+        );
+        assert_eq!(
+            qt.raycast(point![10.0, 50.0], vector![0.0, 0.0], 1.0),
+            None,
+            "Zero direction should hit nothing"
+        );
+    }
+
+    #[test]
+    fn centroid_of_region() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        qt.insert_many(&[
+            point![10.0, 10.0],
+            point![20.0, 40.0],
+            point![30.0, 10.0],
+            point![90.0, 90.0],
+        ]);
+
+        assert_eq!(
+            qt.centroid(&make_rect(0.0, 0.0, 50.0, 50.0)),
+            Some(point![20.0, 20.0]),
+            "Centroid should average the three points within the region"
+        );
+        assert_eq!(
+            qt.centroid(&make_rect(50.0, 0.0, 80.0, 50.0)),
+            None,
+            "Region without items should have no centroid"
+        );
+    }
+
+    #[test]
+    fn min_cell_size_bounds_subdivision() {
+        let mut qt = QuadTree::with_min_cell_size(make_rect(0.0, 0.0, 100.0, 100.0), 2, 10.0);
+        let cluster: Vec<P2> = (0..50)
+            .map(|i| point![61.0 + (i % 5) as f64 * 0.01, 61.0 + (i / 5) as f64 * 0.01])
+            .collect();
+        assert!(
+            qt.insert_many(&cluster).is_empty(),
+            "Cluster should be inserted"
+        );
+        qt.insert(&point![61.005, 61.005]);
+
+        let leaves: Vec<_> = qt.leaves().collect();
+        assert_eq!(leaves.len(), 1, "Cluster should land in a single leaf");
+        let (cell, items) = leaves[0];
+        assert_eq!(items.len(), 51, "Leaf should hold every clustered point");
+        assert_eq!(
+            cell,
+            make_rect(50.0, 50.0, 62.5, 62.5),
+            "Leaf should be the smallest cell whose children would be under the minimum size"
+        );
+        assert_eq!(qt.validate(), Ok(()));
+    }
+
+    #[test]
+    fn cell_of_reflects_local_resolution() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        qt.insert_many(&[
+            point![10.0, 10.0],
+            point![12.0, 12.0],
+            point![14.0, 11.0],
+            point![80.0, 80.0],
+        ]);
+
+        let dense = qt
+            .cell_of(&point![11.0, 11.0])
+            .expect("Point should be in bounds");
+        let sparse = qt
+            .cell_of(&point![70.0, 90.0])
+            .expect("Point should be in bounds");
+        assert!(
+            dense.contains(&point![11.0, 11.0]),
+            "Cell should contain the point"
+        );
+        assert!(
+            dense.end().x - dense.start().x < sparse.end().x - sparse.start().x,
+            "Dense region should have smaller cells than a sparse region"
+        );
+        assert_eq!(sparse, make_rect(50.0, 50.0, 100.0, 100.0));
+        assert_eq!(
+            qt.cell_of(&point![150.0, 50.0]),
+            None,
+            "Point outside of the boundary should have no cell"
+        );
+    }
+
+    #[test]
+    fn get_and_remove_on_internal_edges() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let points = vec![
+            point![10.0, 10.0],
+            point![90.0, 90.0],
+            point![50.0, 50.0],
+            point![50.0, 20.0],
+            point![25.0, 50.0],
+        ];
+        qt.insert_many(&points);
+        qt.insert(&point![75.0, 25.0]);
+
+        for point in &points {
+            assert_eq!(qt.get(point), Some(*point), "Should get {point:?}");
+        }
+        for point in &points {
+            assert_eq!(qt.remove(point), Some(*point), "Should remove {point:?}");
+            assert_eq!(
+                qt.get(point),
+                None,
+                "{point:?} should be gone after removal"
+            );
+        }
+        assert_eq!(
+            qt.remove(&point![50.0, 50.0]),
+            None,
+            "Nothing left to remove"
+        );
+        assert_eq!(
+            qt.count(),
+            1,
+            "Only the separately inserted point should remain"
+        );
+    }
+
+    #[test]
+    fn resize_drops_items_outside_new_boundary() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let points = scattered_points(100);
+        qt.insert_many(&points);
+
+        let new_boundary = make_rect(20.0, 20.0, 60.0, 60.0);
+        let dropped = qt.resize(new_boundary);
+        let (inside, outside): (Vec<P2>, Vec<P2>) =
+            points.iter().partition(|p| new_boundary.contains(p));
+
+        assert_eq!(qt.boundary(), new_boundary, "Boundary should be updated");
+        assert_eq!(
+            dropped.len(),
+            outside.len(),
+            "Points outside should be returned"
+        );
+        assert!(
+            dropped.iter().all(|p| outside.contains(p)),
+            "Only points outside of the new boundary should be returned"
+        );
+        assert_eq!(
+            qt.count(),
+            inside.len(),
+            "Count should exclude dropped points"
+        );
+        let mut remaining = qt.query(&new_boundary);
+        remaining.sort_by(cmp_xy);
+        let mut expected = inside.clone();
+        expected.sort_by(cmp_xy);
+        assert_eq!(remaining, expected, "Points inside should remain queryable");
+        assert_eq!(qt.validate(), Ok(()));
+    }
+
+    #[test]
+    fn query_outside_rect() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        let inside = vec![point![30.0, 30.0], point![40.0, 60.0], point![55.0, 45.0]];
+        let outside = vec![point![10.0, 10.0], point![90.0, 20.0], point![75.0, 85.0]];
+        qt.insert_many(&inside);
+        qt.insert_many(&outside);
+
+        let mut results = qt.query_outside(&make_rect(25.0, 25.0, 75.0, 75.0));
+        results.sort_by(cmp_xy);
+        let mut expected = outside.clone();
+        expected.sort_by(cmp_xy);
+        assert_eq!(
+            results, expected,
+            "Should return exactly the points outside the rect"
+        );
+    }
+
+    #[test]
+    fn insert_reported_counts_subdivisions() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        assert_eq!(
+            qt.insert_reported(&point![10.0, 10.0]),
+            InsertOutcome::Inserted { subdivisions: 0 }
+        );
+        assert_eq!(
+            qt.insert_reported(&point![20.0, 20.0]),
+            InsertOutcome::Inserted { subdivisions: 0 }
+        );
+        assert_eq!(
+            qt.insert_reported(&point![80.0, 80.0]),
+            InsertOutcome::Inserted { subdivisions: 1 },
+            "Exceeding the capacity should subdivide the root"
+        );
+        assert_eq!(
+            qt.insert_reported(&point![15.0, 15.0]),
+            InsertOutcome::Inserted { subdivisions: 2 },
+            "Crowding the first quadrant should subdivide it twice"
+        );
+        assert_eq!(
+            qt.insert_reported(&point![150.0, 15.0]),
+            InsertOutcome::OutOfBounds
+        );
+        assert_eq!(
+            qt.insert_reported(&point![f64::NAN, 15.0]),
+            InsertOutcome::NonFinite
+        );
+        assert_eq!(
+            qt.count(),
+            4,
+            "Only the reported insertions should be counted"
+        );
+    }
+
+    #[test]
+    fn builder_matches_new() {
+        let boundary = make_rect(0.0, 0.0, 100.0, 100.0);
+        let mut built = QuadTree::builder()
+            .boundary(boundary)
+            .node_capacity(3)
+            .build();
+        let mut manual = QuadTree::new(boundary, 3);
+
+        let points = scattered_points(200);
+        assert_eq!(built.insert_many(&points), manual.insert_many(&points));
+        assert_eq!(
+            built.node_boundaries(),
+            manual.node_boundaries(),
+            "Builder-built tree should subdivide like one from new"
+        );
+        let region = make_circle(30.0, 70.0, 20.0);
+        assert_eq!(built.query(&region), manual.query(&region));
+    }
+
+    #[test]
+    fn builder_max_depth() {
+        let mut qt = QuadTree::builder()
+            .boundary(make_rect(0.0, 0.0, 100.0, 100.0))
+            .node_capacity(1)
+            .max_depth(3)
+            .build();
+        qt.insert_many(&scattered_points(200));
+
+        let max_depth = qt.node_boundaries().iter().map(|&(_, d)| d).max();
+        assert_eq!(
+            max_depth,
+            Some(3),
+            "Tree should not be deeper than the maximum"
+        );
+        assert_eq!(qt.count(), 200, "Every point should be stored");
+        assert_eq!(qt.validate(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "without setting a boundary")]
+    fn builder_requires_boundary() {
+        QuadTree::<P2>::builder().node_capacity(4).build();
+    }
+
+    #[test]
+    fn closest_pair_across_quadrants() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        assert_eq!(qt.closest_pair(), None, "Empty tree should have no pair");
+        qt.insert(&point![10.0, 10.0]);
+        assert_eq!(qt.closest_pair(), None, "A single item should have no pair");
+
+        qt.insert_many(&[
+            point![20.0, 10.0],
+            point![49.0, 70.0],
+            point![51.0, 70.5],
+            point![80.0, 20.0],
+            point![90.0, 90.0],
+        ]);
+        let (a, b, distance) = qt.closest_pair().expect("Tree should have a pair");
+        let mut pair = [a, b];
+        pair.sort_by(cmp_xy);
+        assert_eq!(
+            pair,
+            [point![49.0, 70.0], point![51.0, 70.5]],
+            "Closest pair should straddle the vertical quadrant boundary"
+        );
+        assert_eq!(distance, na::distance(&a, &b));
+
+        let points = scattered_points(300);
+        let mut scattered = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        scattered.insert_many(&points);
+        let mut expected = f64::INFINITY;
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                expected = expected.min(na::distance(a, b));
+            }
+        }
+        let (_, _, distance) = scattered.closest_pair().unwrap();
+        assert_eq!(distance, expected, "Should match the brute force distance");
+    }
+
+    #[test]
+    fn density_grid_filter_counts_matching() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        let points: Vec<P2> = (0..100)
+            .map(|i| point![(i % 10) as f64 * 10.0 + 5.0, (i / 10) as f64 * 10.0 + 5.0])
+            .collect();
+        qt.insert_many(&points);
+
+        let region = make_rect(0.0, 0.0, 100.0, 100.0);
+        let all = qt.density_grid(region, 4, 4);
+        let left = qt.density_grid_filter(region, 4, 4, |p| p.x < 50.0);
+        assert_eq!(
+            left.iter().sum::<usize>() * 2,
+            all.iter().sum::<usize>(),
+            "Excluding half of the points should halve the total"
+        );
+        for (cell, (&l, &a)) in left.iter().zip(&all).enumerate() {
+            let expected = if cell % 4 < 2 { a } else { 0 };
+            assert_eq!(
+                l, expected,
+                "Cell {cell} should match the unfiltered bucketing"
+            );
+        }
+    }
+
+    #[test]
+    fn insert_many_reported_keeps_indices() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let points = vec![
+            point![10.0, 10.0],
+            point![150.0, 10.0],
+            point![50.0, 50.0],
+            point![f64::NAN, 20.0],
+            point![90.0, 90.0],
+            point![-5.0, 40.0],
+        ];
+
+        let failed = qt.insert_many_reported(&points);
+        assert_eq!(failed.len(), 3, "Three points should fail");
+        assert_eq!(
+            failed.iter().map(|&(i, _)| i).collect::<Vec<_>>(),
+            vec![1, 3, 5],
+            "Failures should keep their original indices"
+        );
+        assert_eq!(failed[0].1, points[1]);
+        assert_eq!(failed[2].1, points[5]);
+        assert_eq!(qt.count(), 3, "The in-bounds points should be inserted");
+        assert_eq!(qt.validate(), Ok(()));
+    }
+
+    #[test]
+    fn query_with_boxed_shape() {
+        use alloc::boxed::Box;
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        qt.insert_many(&scattered_points(200));
+
+        let shapes: Vec<Box<dyn Shape>> = vec![
+            Box::new(make_rect(10.0, 10.0, 60.0, 40.0)),
+            Box::new(make_circle(50.0, 50.0, 20.0)),
+        ];
+        let mut expected = vec![
+            qt.query(&make_rect(10.0, 10.0, 60.0, 40.0)),
+            qt.query(&make_circle(50.0, 50.0, 20.0)),
+        ];
+        for (shape, expected) in shapes.iter().zip(&mut expected) {
+            let mut results = qt.query_dyn(shape.as_ref());
+            results.sort_by(cmp_xy);
+            expected.sort_by(cmp_xy);
+            assert_eq!(
+                &results, expected,
+                "Boxed shape should query like the concrete shape"
+            );
+        }
+    }
+
+    #[test]
+    fn iter_positions_match_items() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Unit {
+            position: P2,
+            id: usize,
+        }
+
+        impl Point for Unit {
+            fn point(&self) -> P2 {
+                self.position
+            }
+        }
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let units: Vec<Unit> = scattered_points(50)
+            .into_iter()
+            .enumerate()
+            .map(|(id, position)| Unit { position, id })
+            .collect();
+        qt.insert_many(&units);
+
+        let mut ids = vec![];
+        for (position, unit) in qt.iter_positions() {
+            assert_eq!(
+                position,
+                unit.point(),
+                "Position should match the item's point"
+            );
+            ids.push(unit.id);
+        }
+        assert_eq!(ids.len(), qt.count(), "Every item should be yielded once");
+        ids.sort();
+        assert_eq!(ids, (0..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn get_many_preserves_order() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let points = scattered_points(100);
+        qt.insert_many(&points);
+
+        let lookups = vec![
+            points[42],
+            point![200.0, 200.0],
+            points[7],
+            point![50.5, 50.5],
+            points[99],
+            points[7],
+        ];
+        assert_eq!(
+            qt.get_many(&lookups),
+            vec![
+                Some(points[42]),
+                None,
+                Some(points[7]),
+                None,
+                Some(points[99]),
+                Some(points[7]),
+            ],
+            "Results should follow the input order with None for missing points"
+        );
+        assert_eq!(
+            qt.get_many(&points),
+            points.iter().map(|p| qt.get(p)).collect::<Vec<_>>(),
+            "Should match calling get for each point"
+        );
+    }
+
+    #[test]
+    fn root_collapses_when_emptied() {
+        let boundary = make_rect(0.0, 0.0, 100.0, 100.0);
+        let points = scattered_points(40);
+
+        let mut deleted = QuadTree::new(boundary, 2);
+        deleted.insert_many(&points);
+        assert!(deleted.depth() > 0, "Tree should be subdivided");
+        deleted.delete(&make_rect(0.0, 0.0, 50.0, 100.0));
+        deleted.delete(&make_rect(50.0, 0.0, 100.0, 100.0));
+        assert_eq!(deleted.depth(), 0, "Emptied tree should have no depth");
+        assert!(
+            matches!(deleted.root, Node::Empty { .. }),
+            "Root should be empty after deleting every item"
+        );
+
+        let mut removed = QuadTree::new(boundary, 2);
+        removed.insert_many(&points);
+        for point in &points {
+            removed.remove(point);
+        }
+        assert_eq!(removed.depth(), 0);
+        assert!(matches!(removed.root, Node::Empty { .. }));
+
+        let mut popped = QuadTree::new(boundary, 2);
+        popped.reserve(8);
+        popped.insert_many(&points);
+        popped.pop(&boundary);
+        assert_eq!(popped.depth(), 0);
+        assert!(matches!(popped.root, Node::Empty { .. }));
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use nalgebra::point;
 
-    use crate::{
-        shapes::Circle,
-        util::tests::{make_circle, make_rect},
-        Point,
-    };
+    #[test]
+    fn query_with_distance_to_edge() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        qt.insert_many(&[point![50.0, 50.0], point![53.0, 54.0], point![58.0, 50.0]]);
+        qt.insert(&point![90.0, 90.0]);
 
-    use super::*;
+        let mut results = qt.query_with_distance(&make_circle(50.0, 50.0, 10.0));
+        results.sort_by(|a, b| a.1.total_cmp(&b.1));
+        assert_eq!(
+            results,
+            vec![
+                (point![50.0, 50.0], -10.0),
+                (point![53.0, 54.0], -5.0),
+                (point![58.0, 50.0], -2.0),
+            ],
+            "Each item should be paired with its signed distance to the circle's edge"
+        );
+    }
 
     #[test]
-    fn insert_single_item() {
+    fn quantiles_of_coordinates() {
         let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let item = point![25.0, 25.0];
-        assert!(qt.insert(&item), "Should insert item successfully");
+        assert_eq!(qt.quantile_x(0.5), None, "Empty tree should have no median");
+
+        qt.insert_many(&[
+            point![70.0, 5.0],
+            point![10.0, 90.0],
+            point![40.0, 30.0],
+            point![90.0, 60.0],
+            point![20.0, 10.0],
+        ]);
+        assert_eq!(
+            qt.quantile_x(0.5),
+            Some(40.0),
+            "Median of an odd count is the middle"
+        );
+        assert_eq!(qt.quantile_y(0.5), Some(30.0));
+        assert_eq!(qt.quantile_x(0.0), Some(10.0), "0 quantile is the minimum");
+        assert_eq!(qt.quantile_x(1.0), Some(90.0), "1 quantile is the maximum");
+        assert_eq!(qt.quantile_x(0.25), Some(20.0));
+
+        qt.insert(&point![50.0, 50.0]);
+        assert_eq!(
+            qt.quantile_x(0.5),
+            Some(45.0),
+            "Median of an even count is the mean of the middle two"
+        );
+        assert_eq!(qt.quantile_y(0.5), Some(40.0));
+        assert_eq!(
+            qt.quantile_x(1.5),
+            None,
+            "Quantile outside [0, 1] is invalid"
+        );
     }
 
     #[test]
-    fn insert_multiple_items() {
+    fn nearest_excluding_skips_positions() {
         let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let points = vec![
-            point![10.0, 10.0],
-            point![150.0, 150.0], // This should fail (out of bounds)
-            point![20.0, 20.0],
-            point![120.0, 120.0], // This should fail (out of bounds)
+        let points = [
+            point![50.0, 50.0],
+            point![53.0, 50.0],
+            point![40.0, 40.0],
+            point![90.0, 10.0],
         ];
+        qt.insert_many(&points);
 
-        let failed_inserts = qt.insert_many(&points);
-
-        assert_eq!(failed_inserts.len(), 2, "Should return 2 failed inserts");
-        assert!(
-            failed_inserts.contains(&points[1]),
-            "Should include point (150, 150) as failed"
+        let target = point![51.0, 50.0];
+        assert_eq!(qt.nearest_excluding(&target, &[]), Some(points[0]));
+        assert_eq!(
+            qt.nearest_excluding(&target, &[points[0]]),
+            Some(points[1]),
+            "Should return the second-closest point when the closest is excluded"
         );
-        assert!(
-            failed_inserts.contains(&points[3]),
-            "Should include point (120, 120) as failed"
+        assert_eq!(
+            qt.nearest_excluding(&target, &points),
+            None,
+            "Should find nothing when every point is excluded"
         );
+    }
 
-        // Ensure that successful points are indeed in the QuadTree
+    #[test]
+    fn update_matching_repositions_items() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        qt.insert_many(&[
+            point![10.0, 10.0],
+            point![20.0, 15.0],
+            point![15.0, 25.0],
+            point![80.0, 80.0],
+            point![95.0, 95.0],
+        ]);
+
+        let left = qt.update_matching(&make_rect(0.0, 0.0, 30.0, 30.0), |p| {
+            *p += vector![50.0, 0.0]
+        });
+        assert!(left.is_empty(), "No items should leave the boundary");
+        assert_eq!(qt.count(), 5, "Count should be preserved");
         assert!(
-            qt.get(&points[0].point()).is_some(),
-            "Point (10, 10) should be successfully inserted"
+            qt.query(&make_rect(0.0, 0.0, 30.0, 30.0)).is_empty(),
+            "Items should no longer be at their old positions"
         );
-        assert!(
-            qt.get(&points[2].point()).is_some(),
-            "Point (20, 20) should be successfully inserted"
+        let mut moved = qt.query(&make_rect(50.0, 0.0, 80.0, 30.0));
+        moved.sort_by(cmp_xy);
+        assert_eq!(
+            moved,
+            vec![point![60.0, 10.0], point![65.0, 25.0], point![70.0, 15.0]],
+            "Items should be queryable at their new positions"
         );
 
-        // Check count correctness
+        let left = qt.update_matching(&make_rect(75.0, 75.0, 100.0, 100.0), |p| {
+            *p += vector![10.0, 0.0]
+        });
+        assert_eq!(
+            left,
+            vec![point![105.0, 95.0]],
+            "Items moved out of bounds should be returned"
+        );
         assert_eq!(
             qt.count(),
-            2,
-            "Count should be 2 for successfully inserted items"
+            4,
+            "Items that left the boundary should not be counted"
         );
+        assert_eq!(qt.get(&point![90.0, 80.0]), Some(point![90.0, 80.0]));
+        assert_eq!(qt.validate(), Ok(()));
     }
 
     #[test]
-    fn insert_item_out_of_bounds() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let item = point![150.0, 150.0];
-        assert!(!qt.insert(&item), "Should not insert item outside bounds");
+    fn occupied_cells_reflects_dispersion() {
+        let mut clustered = QuadTree::with_min_cell_size(make_rect(0.0, 0.0, 100.0, 100.0), 1, 5.0);
+        let mut spread = QuadTree::with_min_cell_size(make_rect(0.0, 0.0, 100.0, 100.0), 1, 5.0);
+        assert_eq!(
+            clustered.occupied_cells(),
+            0,
+            "Empty tree should occupy no cells"
+        );
+
+        clustered.insert_many(
+            &(0..16)
+                .map(|i| point![10.0 + (i % 4) as f64, 10.0 + (i / 4) as f64])
+                .collect::<Vec<_>>(),
+        );
+        spread.insert_many(
+            &(0..16)
+                .map(|i| point![5.0 + (i % 4) as f64 * 25.0, 5.0 + (i / 4) as f64 * 25.0])
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            spread.occupied_cells(),
+            16,
+            "Spread points should each have a cell"
+        );
+        assert!(
+            clustered.occupied_cells() < spread.occupied_cells(),
+            "Clustered points should occupy fewer cells than spread points"
+        );
+
+        spread.delete(&make_rect(0.0, 0.0, 50.0, 50.0));
+        assert_eq!(
+            spread.occupied_cells(),
+            12,
+            "Leaves emptied by deletion should not be counted"
+        );
     }
 
     #[test]
-    fn insert_multiple_items_subdivision() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
-        let points = vec![point![20.0, 20.0], point![40.0, 40.0], point![60.0, 60.0]];
+    fn key_fn_positions_items_without_point() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Station {
+            name: &'static str,
+            x: f64,
+            y: f64,
+        }
 
-        qt.insert_many(&points);
+        let station = |name, x, y| Station { name, x, y };
+        let mut qt = QuadTree::new_with(make_rect(0.0, 0.0, 100.0, 100.0), 1, |s: &Station| {
+            point![s.x, s.y]
+        });
+        let stations = [
+            station("north", 50.0, 90.0),
+            station("south", 50.0, 10.0),
+            station("east", 90.0, 50.0),
+            station("west", 10.0, 50.0),
+        ];
+        assert!(qt.insert_many(&stations).is_empty());
+        assert!(!qt.insert(&station("far", 150.0, 50.0)));
+        assert_eq!(qt.count(), 4);
+        assert_eq!(qt.depth(), 2, "Items should be subdivided by their keys");
 
-        match qt.root {
-            Node::Internal { children, .. } => {
-                assert_eq!(
-                    children.len(),
-                    4,
-                    "Should have four children after subdivision"
-                );
-            }
-            _ => panic!("QuadTree should have subdivided into an internal node"),
-        }
+        assert_eq!(
+            qt.nearest(&point![80.0, 60.0]).map(|s| s.name),
+            Some("east"),
+            "Nearest should use the key function"
+        );
+        assert_eq!(qt.get(&point![10.0, 50.0]), Some(stations[3].clone()));
+        let mut found = qt
+            .query(&make_rect(0.0, 40.0, 100.0, 100.0))
+            .into_iter()
+            .map(|s| s.name)
+            .collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, ["east", "north", "west"]);
+        assert_eq!(
+            qt.remove(&point![50.0, 10.0]).map(|s| s.name),
+            Some("south")
+        );
+        assert_eq!(qt.validate(), Ok(()));
     }
 
     #[test]
-    fn get_item() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let point = point![20.0, 20.0];
-        qt.insert(&point);
+    fn quadtree_by_indexes_tuples() {
+        let mut qt: QuadTreeBy<(f64, f64), _> =
+            QuadTree::new_with(make_rect(0.0, 0.0, 100.0, 100.0), 2, |&(x, y)| point![x, y]);
+        let tuples = [
+            (10.0, 10.0),
+            (20.0, 80.0),
+            (60.0, 40.0),
+            (90.0, 90.0),
+            (55.0, 45.0),
+        ];
+        assert!(qt.insert_many(&tuples).is_empty());
+        assert_eq!(qt.count(), 5);
 
+        let mut found = qt.query(&make_circle(57.0, 43.0, 5.0));
+        found.sort_by(|a, b| a.0.total_cmp(&b.0));
+        assert_eq!(found, [(55.0, 45.0), (60.0, 40.0)]);
+        assert_eq!(qt.nearest(&point![85.0, 95.0]), Some((90.0, 90.0)));
         assert_eq!(
-            qt.get(&point),
-            Some(point),
-            "Should find the inserted point"
+            qt.pop(&make_rect(0.0, 0.0, 50.0, 50.0)),
+            [(10.0, 10.0)],
+            "Tuples should be removed by their keyed position"
         );
         assert!(
-            qt.get(&point![30.0, 30.0]).is_none(),
-            "Should not find a point that was not inserted"
+            qt.iter_positions().all(|(p, &(x, y))| p == point![x, y]),
+            "Positions should come from the key function"
         );
+        assert_eq!(qt.validate(), Ok(()));
     }
 
     #[test]
-    fn query_rectangular() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let results = qt.query(&make_rect(10.0, 10.0, 50.0, 50.0));
-        assert!(results.is_empty(), "Should be empty for an empty tree");
+    fn query_grouped_matches_flat_query() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.insert_many(&scattered_points(200));
+        let circle = make_circle(40.0, 60.0, 30.0);
 
-        let item = point![25.0, 25.0];
-        qt.insert(&item);
-        let results = qt.query(&make_rect(20.0, 20.0, 30.0, 30.0));
-        assert_eq!(results.len(), 1, "Should find one item in the range");
+        let groups = qt.query_grouped(&circle);
+        assert!(groups.len() > 1, "Items should span several leaves");
+        for (boundary, items) in &groups {
+            assert!(!items.is_empty(), "Empty groups should be omitted");
+            assert!(
+                items
+                    .iter()
+                    .all(|p| boundary.contains(p) && circle.contains(p)),
+                "Each group should hold matching items from its leaf"
+            );
+        }
+
+        let mut grouped = groups
+            .into_iter()
+            .flat_map(|(_, items)| items)
+            .collect::<Vec<_>>();
+        let mut flat = qt.query(&circle);
+        grouped.sort_by(cmp_xy);
+        flat.sort_by(cmp_xy);
         assert_eq!(
-            results[0].point(),
-            item.point(),
-            "The point should match the inserted item"
+            grouped, flat,
+            "Flattened groups should equal the flat query"
         );
+    }
 
-        let results = qt.query(&make_rect(70.0, 70.0, 80.0, 80.0));
-        assert!(
-            results.is_empty(),
-            "Should not find any items outside the range"
+    #[test]
+    fn nearest_approx_within_bound() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        qt.insert_many(&scattered_points(300));
+
+        for target in scattered_points(20)
+            .iter()
+            .map(|p| point![100.0 - p.x, p.y])
+        {
+            let exact = qt.nearest(&target).unwrap();
+            assert_eq!(
+                qt.nearest_approx(&target, 0.0),
+                Some(exact),
+                "eps = 0 should match the exact nearest"
+            );
+
+            let approx = qt.nearest_approx(&target, 0.5).unwrap();
+            assert!(
+                na::distance(&approx, &target) <= 1.5 * na::distance(&exact, &target),
+                "Approximate nearest should be within a factor of 1 + eps"
+            );
+        }
+        assert_eq!(
+            QuadTree::<P2>::new(make_rect(0.0, 0.0, 1.0, 1.0), 1)
+                .nearest_approx(&point![0.5, 0.5], 1.0),
+            None
         );
     }
 
     #[test]
-    fn query_ref_rectangular() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let item = point![25.0, 25.0];
-        qt.insert(&item);
-        let results = qt.query_ref(&make_rect(20.0, 20.0, 30.0, 30.0));
-        assert_eq!(results.len(), 1, "Should find one item in the range");
+    fn try_new_rejects_invalid_boundaries() {
+        let p = point![5.0, 5.0];
         assert_eq!(
-            results[0].point(),
-            item.point(),
-            "The point should match the inserted item"
+            QuadTree::<P2>::try_new(Rect::new(p, p), 4).err(),
+            Some(QuadTreeError::DegenerateBoundary),
+            "Zero-area boundary should be rejected"
+        );
+        assert_eq!(
+            QuadTree::<P2>::try_new(make_rect(10.0, 0.0, 0.0, 10.0), 4).err(),
+            Some(QuadTreeError::DegenerateBoundary),
+            "Negative width should be rejected"
+        );
+        assert_eq!(
+            QuadTree::<P2>::try_new(make_rect(0.0, 0.0, f64::INFINITY, 10.0), 4).err(),
+            Some(QuadTreeError::NonFiniteBoundary)
         );
+
+        let mut qt = QuadTree::try_new(make_rect(0.0, 0.0, 10.0, 10.0), 4)
+            .expect("Valid boundary should be accepted");
+        assert!(qt.insert(&p));
     }
 
     #[test]
-    fn query_rectangular_internal_nodes_multiple_items() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let item1 = point![25.0, 25.0];
-        let item2 = point![75.0, 75.0];
-        qt.insert(&item1);
-        qt.insert(&item2);
+    fn drain_filter_removes_matching_items() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        qt.insert_many(&scattered_points(100));
+        let is_left = |p: &P2| p.x < 30.0;
+        let expected = qt.iter().filter(|p| is_left(p)).count();
 
-        let results = qt.query(&make_rect(20.0, 20.0, 80.0, 80.0));
-        assert_eq!(results.len(), 2, "Should find both items in the range");
+        let drained = qt.drain_filter(is_left);
+        assert_eq!(drained.len(), expected);
+        assert!(
+            drained.iter().all(is_left),
+            "Drained items should satisfy the predicate"
+        );
+        assert!(
+            !qt.iter().any(is_left),
+            "Retained items should not satisfy the predicate"
+        );
+        assert_eq!(
+            qt.count(),
+            100 - expected,
+            "Count should exclude drained items"
+        );
+        assert_eq!(qt.validate(), Ok(()));
 
-        let results = qt.query(&make_rect(70.0, 70.0, 80.0, 80.0));
-        assert_eq!(results.len(), 1, "Should find one item in the range");
+        assert_eq!(qt.drain_filter(|_| true).len(), 100 - expected);
         assert_eq!(
-            results[0].point(),
-            item2.point(),
-            "The point should match the second inserted item"
+            qt.depth(),
+            0,
+            "Draining everything should collapse the tree"
         );
+    }
 
-        let results = qt.query(&make_rect(200.0, 200.0, 300.0, 300.0));
+    #[test]
+    fn query_window_returns_items_and_cells() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 3);
+        qt.insert_many(&scattered_points(120));
+        let view = make_rect(20.0, 35.0, 70.0, 60.0);
+
+        let (items, cells) = qt.query_window(view);
+        assert!(!cells.is_empty());
         assert!(
-            results.is_empty(),
-            "Should not find any items outside the range"
+            cells.iter().all(|cell| cell.intersects(&view)),
+            "Every cell should intersect the view"
+        );
+        assert!(
+            items.iter().all(|p| view.contains(p)),
+            "Every item should lie within the view"
+        );
+
+        let mut found = items.into_iter().copied().collect::<Vec<_>>();
+        let mut expected = qt.query(&view);
+        found.sort_by(cmp_xy);
+        expected.sort_by(cmp_xy);
+        assert_eq!(found, expected, "Items should match a regular query");
+
+        let area = cells.iter().map(|cell| cell.area()).sum::<f64>();
+        assert!(
+            area >= view.area(),
+            "Cells should cover the part of the boundary within the view"
         );
     }
 
     #[test]
-    fn query_rectangular_boundary_edge_overlap() {
+    fn default_subdivider_matches_quarter() {
+        let points = scattered_points(200);
         let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
-        let edge_point = point![100.0, 50.0]; // Exactly on the boundary edge
-        qt.insert(&edge_point);
-        let query_shape = make_rect(95.0, 45.0, 105.0, 55.0);
-        let results = qt.query(&query_shape);
-        assert_eq!(results.len(), 1, "Should find the edge point.");
+        let mut qt_sub =
+            QuadTree::new_with_subdivider(make_rect(0.0, 0.0, 100.0, 100.0), 4, Quarter);
+        qt.insert_many(&points);
+        qt_sub.insert_many(&points);
+
+        assert_eq!(qt.node_boundaries(), qt_sub.node_boundaries());
+        let region = make_circle(40.0, 60.0, 25.0);
+        assert_eq!(qt.query(&region), qt_sub.query(&region));
+        assert_eq!(qt_sub.validate(), Ok(()));
     }
 
     #[test]
-    fn query_circular_empty_tree() {
-        let mut qt = QuadTree::<P2>::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let circle = make_circle(50.0, 50.0, 10.0);
-        let results = qt.query(&circle);
-        assert!(results.is_empty(), "Should be empty for an empty tree");
+    fn custom_subdivider_routes_by_split_point() {
+        // Split each node a quarter of the way along each axis
+        let weighted = |rect: &Rect| {
+            let split = rect.start() + (rect.end() - rect.start()) * 0.25;
+            let (min, max) = (rect.start(), rect.end());
+            [
+                Rect::new(min, split),
+                Rect::new(point![split.x, min.y], point![max.x, split.y]),
+                Rect::new(point![min.x, split.y], point![split.x, max.y]),
+                Rect::new(split, max),
+            ]
+        };
+        let mut qt = QuadTree::new_with_subdivider(make_rect(0.0, 0.0, 100.0, 100.0), 1, weighted);
+        for p in [point![10.0, 10.0], point![30.0, 10.0], point![10.0, 30.0]] {
+            assert!(qt.insert(&p));
+        }
 
-        let item = point![25.0, 25.0];
-        qt.insert(&item);
-        let circle = make_circle(20.0, 20.0, 10.0);
-        let results = qt.query(&circle);
-        assert_eq!(results.len(), 1, "Should find one item within the circle");
+        assert_eq!(qt.validate(), Ok(()));
         assert_eq!(
-            results[0].point(),
-            item.point(),
-            "The point should match the inserted item"
+            qt.cell_of(&point![30.0, 10.0]),
+            Some(make_rect(25.0, 0.0, 100.0, 25.0)),
+            "Point right of the split should be in the wide child"
         );
-
-        let circle = make_circle(80.0, 80.0, 5.0);
-        let results = qt.query(&circle);
-        assert!(
-            results.is_empty(),
-            "Should not find any items outside the circle"
+        assert_eq!(
+            qt.cell_of(&point![10.0, 30.0]),
+            Some(make_rect(0.0, 25.0, 25.0, 100.0))
         );
+        assert_eq!(qt.get(&point![30.0, 10.0]), Some(point![30.0, 10.0]));
+        assert_eq!(
+            qt.query(&make_rect(20.0, 0.0, 40.0, 20.0)),
+            vec![point![30.0, 10.0]]
+        );
+        assert_eq!(qt.remove(&point![10.0, 30.0]), Some(point![10.0, 30.0]));
+        assert_eq!(qt.count(), 2);
     }
 
     #[test]
-    fn query_circular_internal_nodes_multiple_items() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let item1 = point![35.0, 35.0];
-        let item2 = point![65.0, 65.0];
-        qt.insert(&item1);
-        qt.insert(&item2);
+    fn new_auto_capacity_in_sane_range() {
+        let boundary = make_rect(0.0, 0.0, 100.0, 100.0);
+        for (expected, min, max) in [(0, 8, 8), (10, 8, 16), (1_000, 8, 32), (1_000_000, 32, 64)] {
+            let qt = QuadTree::<P2>::new_auto(boundary, expected);
+            let capacity = qt.capacity.get(&boundary);
+            assert!(
+                (min..=max).contains(&capacity),
+                "Capacity {capacity} for {expected} items should be in {min}..={max}"
+            );
+        }
+        assert!(auto_node_capacity(100) <= auto_node_capacity(1_000_000));
 
-        let circle = make_circle(50.0, 50.0, 30.0);
-        let results = qt.query(&circle);
-        assert_eq!(results.len(), 2, "Should find both items within the circle");
+        let mut qt = QuadTree::new_auto(boundary, 200);
+        let points = scattered_points(200);
+        assert!(qt.insert_many(&points).is_empty());
+        assert_eq!(qt.count(), 200);
+        assert_eq!(qt.validate(), Ok(()));
+    }
 
-        let circle = make_circle(70.0, 70.0, 10.0);
-        let results = qt.query(&circle);
-        assert_eq!(results.len(), 1, "Should find one item within the circle");
-        assert_eq!(
-            results[0].point(),
-            item2.point(),
-            "The point should match the second inserted item"
-        );
+    #[test]
+    fn circle_query_prunes_corner_cells() {
+        use core::cell::Cell;
+
+        // Counts the leaves a query visits, optionally pruning with the bounding box like a rect
+        struct Counted<'a> {
+            circle: Circle,
+            bbox: bool,
+            leaves: &'a Cell<usize>,
+        }
+
+        impl Shape for Counted<'_> {
+            fn start(&self) -> P2 {
+                self.circle.start()
+            }
+
+            fn end(&self) -> P2 {
+                self.circle.end()
+            }
+
+            fn center(&self) -> P2 {
+                self.circle.center()
+            }
+
+            fn contains(&self, point: &P2) -> bool {
+                self.circle.contains(point)
+            }
+
+            fn intersects(&self, other: &Self) -> bool {
+                self.circle.intersects(&other.circle)
+            }
+
+            fn contains_rect(&self, rect: &Rect) -> bool {
+                self.leaves.set(self.leaves.get() + 1);
+                self.circle.contains_rect(rect)
+            }
+
+            fn intersects_rect(&self, rect: &Rect) -> bool {
+                if self.bbox {
+                    rect.intersects(&self.circle.rect())
+                } else {
+                    self.circle.intersects_rect(rect)
+                }
+            }
+        }
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.insert_many(&scattered_points(2000));
+        let circle = make_circle(50.0, 50.0, 45.0);
+
+        let query = |bbox| {
+            let leaves = Cell::new(0);
+            let shape = Counted {
+                circle,
+                bbox,
+                leaves: &leaves,
+            };
+            let mut found = qt.query(&shape);
+            found.sort_by(cmp_xy);
+            (found, leaves.get())
+        };
+        let (exact, exact_leaves) = query(false);
+        let (bbox, bbox_leaves) = query(true);
 
-        let circle = make_circle(200.0, 200.0, 50.0);
-        let results = qt.query(&circle);
+        assert_eq!(exact, bbox, "Pruning should not change the results");
         assert!(
-            results.is_empty(),
-            "Should not find any items outside the circle"
+            exact_leaves < bbox_leaves,
+            "Circle pruning visited {exact_leaves} leaves, bounding box pruning visited {bbox_leaves}"
         );
     }
 
     #[test]
-    fn query_filter_exclude_point() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let items = vec![point![40.0, 40.0], point![50.0, 50.0], point![60.0, 60.0]];
-        qt.insert_many(&items);
-
-        let area = Circle::new(items[1], 20.0);
-        let results = qt.query_filter(&area, |p| p.point() != area.center());
+    fn is_on_boundary_edges() {
+        let qt = QuadTree::<P2>::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        assert!(qt.is_on_boundary(&point![100.0, 50.0]));
         assert!(
-            !results.contains(&items[1]),
-            "Should not find the excluded point"
+            qt.is_on_boundary(&point![0.0, 0.0]),
+            "Corners are on the boundary"
+        );
+        assert!(qt.is_on_boundary(&point![30.0, 100.0]));
+        assert!(!qt.is_on_boundary(&point![50.0, 50.0]));
+        assert!(
+            !qt.is_on_boundary(&point![100.0, 150.0]),
+            "Points on an edge's line but outside of the boundary are not on the boundary"
         );
-        assert_eq!(results.len(), 2, "Should find two points within the circle");
     }
 
     #[test]
-    fn query_ref_filter_exclude_point() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let items = vec![point![40.0, 40.0], point![50.0, 50.0], point![60.0, 60.0]];
-        qt.insert_many(&items);
+    fn clusters_connect_within_radius() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let left = [point![10.0, 10.0], point![12.0, 11.0], point![11.0, 13.0]];
+        let right = [point![80.0, 80.0], point![82.0, 79.0]];
+        qt.insert_many(&left);
+        qt.insert_many(&right);
 
-        let area = Circle::new(items[1], 20.0);
-        let results = qt.query_ref_filter(&area, |p| p.point() != area.center());
-        assert!(
-            !results.contains(&&items[1]),
-            "Should not find the excluded point"
+        let mut clusters = qt.clusters(5.0);
+        assert_eq!(
+            clusters.len(),
+            2,
+            "Separated groups should form two clusters"
+        );
+        for cluster in &mut clusters {
+            cluster.sort_by(cmp_xy);
+        }
+        clusters.sort_by_key(|c| c.len());
+        assert_eq!(clusters[0], right);
+        let mut expected = left.to_vec();
+        expected.sort_by(cmp_xy);
+        assert_eq!(clusters[1], expected);
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let chain: Vec<P2> = (0..20)
+            .map(|i| point![5.0 + i as f64 * 4.5, 50.0])
+            .collect();
+        qt.insert_many(&chain);
+        let clusters = qt.clusters(5.0);
+        assert_eq!(
+            clusters.len(),
+            1,
+            "A chain of close points should form one cluster"
+        );
+        assert_eq!(clusters[0].len(), 20);
+        assert_eq!(
+            qt.clusters(4.0).len(),
+            20,
+            "Links longer than the radius should break"
+        );
+        assert!(QuadTree::<P2>::new(qt.boundary(), 2)
+            .clusters(5.0)
+            .is_empty());
+
+        for radius in [-1.0, f64::NAN] {
+            assert_eq!(
+                qt.clusters(radius).len(),
+                20,
+                "A negative or NaN radius should leave every item alone"
+            );
+        }
+
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.insert_many(&[point![20.0, 20.0]; 3]);
+        qt.insert_many(&[point![60.0, 60.0]; 2]);
+        let mut sizes: Vec<usize> = qt.clusters(0.0).iter().map(Vec::len).collect();
+        sizes.sort_unstable();
+        assert_eq!(
+            sizes,
+            [2, 3],
+            "Items sharing a position should be linked even with a zero radius"
         );
-        assert_eq!(results.len(), 2, "Should find two points within the circle");
     }
 
     #[test]
-    fn delete_rect() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let points = vec![point![10.0, 10.0], point![30.0, 10.0], point![10.0, 30.0]];
-        qt.insert_many(&points);
+    fn query_rect_conversions() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.insert_many(&scattered_points(200));
+        let expected = qt.query(&Rect::new(point![10.0, 20.0], point![50.0, 70.0]));
+        assert!(!expected.is_empty());
 
-        let deletion_shape = make_rect(5.0, 5.0, 35.0, 15.0);
-        let deleted = qt.delete(&deletion_shape);
-        assert_eq!(deleted, 2, "Two items were deleted");
-        assert_eq!(qt.count(), 1, "One item remains in tree");
-        assert!(
-            qt.get(&points[0]).is_none(),
-            "Point at (10.0, 10.0) should have been deleted"
-        );
-        assert!(
-            qt.get(&points[1]).is_none(),
-            "Point at (30.0, 10.0) should have been deleted"
-        );
-        assert!(
-            qt.get(&points[2]).is_some(),
-            "Point at (10.0, 30.0) should still exist"
+        assert_eq!(
+            qt.query_rect((point![10.0, 20.0], point![50.0, 70.0])),
+            expected
         );
+        assert_eq!(qt.query_rect([10.0, 20.0, 50.0, 70.0]), expected);
+        assert_eq!(qt.query_rect(make_rect(10.0, 20.0, 50.0, 70.0)), expected);
     }
 
     #[test]
-    fn delete_rect_bounds() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let points = vec![point![10.0, 10.0], point![20.0, 20.0], point![30.0, 30.0]];
-        for point in &points {
-            qt.insert(point);
-        }
-        assert_eq!(qt.count(), 3, "Three items should be in the tree");
+    fn insert_notify_reports_relocated_items() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        let mut moved = vec![];
+        assert!(qt.insert_notify(&point![10.0, 10.0], |p| moved.push(*p)));
+        assert!(qt.insert_notify(&point![60.0, 60.0], |p| moved.push(*p)));
+        assert!(moved.is_empty(), "Filling a leaf should not move items");
 
-        let deletion_shape = make_rect(15.0, 15.0, 25.0, 25.0);
-        let deleted = qt.delete(&deletion_shape);
-        assert_eq!(deleted, 1, "One item was deleted");
-        assert_eq!(qt.count(), 2, "Two items remain in tree");
-        assert!(
-            qt.get(&points[0]).is_some(),
-            "Point at (10.0, 10.0) should still exist"
+        assert!(qt.insert_notify(&point![70.0, 20.0], |p| moved.push(*p)));
+        assert_eq!(
+            moved,
+            vec![point![10.0, 10.0], point![60.0, 60.0]],
+            "Subdividing the root should move its items"
         );
+        assert_eq!(qt.depth(), 1);
+
+        moved.clear();
+        assert!(qt.insert_notify(&point![20.0, 80.0], |p| moved.push(*p)));
         assert!(
-            qt.get(&points[1]).is_none(),
-            "Point at (20.0, 20.0) should have been deleted"
+            moved.is_empty(),
+            "Inserting into an empty cell should not move items"
         );
-        assert!(
-            qt.get(&points[2]).is_some(),
-            "Point at (30.0, 30.0) should still exist"
+
+        qt.insert_notify(&point![15.0, 15.0], |p| moved.push(*p));
+        assert!(moved.is_empty());
+        qt.insert_notify(&point![40.0, 40.0], |p| moved.push(*p));
+        assert_eq!(
+            moved,
+            vec![point![10.0, 10.0], point![15.0, 15.0]],
+            "Only the items of the subdivided leaf should be moved"
         );
+        assert!(!qt.insert_notify(&point![150.0, 50.0], |_| panic!("Nothing should move")));
+        assert_eq!(qt.count(), 6);
     }
 
     #[test]
-    fn delete_rect_multi_level() {
+    fn query_with_depth_reflects_density() {
         let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
-        // Points to cause subdivisions
-        let points = [
+        let cluster = [
             point![10.0, 10.0],
-            point![90.0, 90.0],
-            point![10.0, 90.0],
-            point![90.0, 10.0],
-            point![50.0, 50.0],
-            point![30.0, 30.0],
+            point![11.0, 12.0],
+            point![12.0, 11.0],
+            point![13.0, 13.0],
         ];
-        for p in &points {
-            qt.insert(p);
+        qt.insert_many(&cluster);
+        qt.insert(&point![80.0, 80.0]);
+
+        let results = qt.query_with_depth(&qt.boundary());
+        assert_eq!(results.len(), 5);
+        let isolated = results
+            .iter()
+            .find(|(p, _)| *p == point![80.0, 80.0])
+            .map(|&(_, depth)| depth)
+            .unwrap();
+        assert_eq!(isolated, 1);
+        for (p, depth) in &results {
+            if cluster.contains(p) {
+                assert!(
+                    *depth > isolated,
+                    "Clustered item {p} should be deeper than the isolated item"
+                );
+            }
         }
-        let deletion_shape = make_rect(25.0, 25.0, 75.0, 75.0);
-        qt.delete(&deletion_shape);
-        let results = qt.query(&make_rect(0.0, 0.0, 100.0, 100.0));
+
         assert_eq!(
-            results.len(),
-            4,
-            "Should have 4 points left after partial deletion."
+            qt.query_with_depth(&make_circle(80.0, 80.0, 5.0)),
+            vec![(point![80.0, 80.0], 1)]
         );
     }
 
     #[test]
-    fn delete_filter_exclude_point() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let points = vec![point![15.0, 15.0], point![20.0, 20.0], point![25.0, 25.0]];
-        qt.insert_many(&points);
+    fn query_ref_without_clone() {
+        #[derive(Debug, PartialEq)]
+        struct Entity {
+            position: P2,
+            id: u32,
+        }
 
-        let area = make_rect(10.0, 10.0, 30.0, 30.0);
-        let deleted = qt.delete_filter(&area, |p| p.point() != area.center());
-        assert_eq!(deleted, 2, "Two items were deleted");
-        assert_eq!(qt.count(), 1, "One item remains in tree");
-        assert!(
-            qt.get(&points[0]).is_none(),
-            "Point at (15.0, 15.0) should have been deleted"
+        impl Point for Entity {
+            fn point(&self) -> P2 {
+                self.position
+            }
+        }
+
+        let entities = (0..50)
+            .map(|id| Entity {
+                position: point![(id * 7 % 100) as f64, (id * 13 % 100) as f64],
+                id,
+            })
+            .chain([Entity {
+                position: point![150.0, 50.0],
+                id: 99,
+            }])
+            .collect();
+        let (qt, rejected) = QuadTree::from_vec(make_rect(0.0, 0.0, 100.0, 100.0), 4, entities);
+        assert_eq!(rejected.iter().map(|e| e.id).collect::<Vec<_>>(), vec![99]);
+        assert_eq!(qt.count(), 50);
+        assert!(qt.depth() > 0, "Tree should have subdivided");
+        assert_eq!(qt.validate(), Ok(()));
+
+        let region = make_rect(0.0, 0.0, 30.0, 30.0);
+        let mut ids: Vec<u32> = qt.query_ref(&region).iter().map(|e| e.id).collect();
+        ids.sort();
+        let mut expected: Vec<u32> = qt
+            .iter()
+            .filter(|e| region.contains(&e.position))
+            .map(|e| e.id)
+            .collect();
+        expected.sort();
+        assert!(!ids.is_empty());
+        assert_eq!(ids, expected);
+        assert_eq!(
+            qt.nearest_iter(point![7.0, 13.0]).next().map(|e| e.id),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn mutate_without_clone() {
+        #[derive(Debug, PartialEq)]
+        struct Entity {
+            position: P2,
+            id: u32,
+        }
+
+        impl Point for Entity {
+            fn point(&self) -> P2 {
+                self.position
+            }
+        }
+
+        let entities = (0..50)
+            .map(|id| Entity {
+                position: point![(id * 7 % 100) as f64, (id * 13 % 100) as f64],
+                id,
+            })
+            .collect();
+        let (mut qt, _) = QuadTree::from_vec(make_rect(0.0, 0.0, 100.0, 100.0), 4, entities);
+
+        assert_eq!(
+            qt.remove(&point![7.0, 13.0]).map(|e| e.id),
+            Some(1),
+            "Should remove an item that isn't Clone"
         );
+        let region = make_rect(0.0, 0.0, 30.0, 30.0);
+        let popped = qt.pop(&region);
         assert!(
-            qt.get(&points[1]).is_some(),
-            "Point at (20.0, 20.0) should still exist"
+            popped.iter().all(|e| region.contains(&e.position)),
+            "Should pop only items within the region"
+        );
+        let deleted = qt.delete(&make_rect(70.0, 70.0, 100.0, 100.0));
+        assert_eq!(
+            qt.count(),
+            49 - popped.len() - deleted,
+            "Count should reflect every removal"
         );
         assert!(
-            qt.get(&points[2]).is_none(),
-            "Point at (25.0, 25.0) should have been deleted"
+            qt.resize(make_rect(0.0, 0.0, 50.0, 50.0))
+                .iter()
+                .all(|e| e.position.x > 50.0 || e.position.y > 50.0),
+            "Only items outside of the new boundary should be returned"
         );
+        assert_eq!(qt.validate(), Ok(()));
     }
 
     #[test]
-    fn test_pop_function() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let points = vec![
-            point![10.0, 10.0],
-            point![20.0, 20.0],
-            point![30.0, 30.0],
-            point![40.0, 40.0],
-        ];
-
+    fn pop_into_matches_pop() {
+        let points = scattered_points(300);
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
         qt.insert_many(&points);
+        let mut expected_qt = qt.clone();
+        let region = make_circle(40.0, 55.0, 30.0);
 
-        assert_eq!(qt.count(), 4, "All four points were inserted");
+        let mut sunk = vec![];
+        let popped = qt.pop_into(&region, |item| sunk.push(item));
+        let expected = expected_qt.pop(&region);
+        assert!(!expected.is_empty());
+        assert_eq!(popped, expected.len());
+        assert_eq!(sunk, expected);
+        assert_eq!(qt.count(), 300 - popped);
+        assert_eq!(qt.count(), expected_qt.count());
+        assert_eq!(qt.node_boundaries(), expected_qt.node_boundaries());
+        assert!(qt.query(&region).is_empty());
+        assert_eq!(qt.validate(), Ok(()));
 
-        let results = qt.pop(&make_rect(15.0, 15.0, 35.0, 35.0));
+        assert_eq!(qt.pop_into(&qt.boundary(), drop), 300 - popped);
+        assert_eq!(qt.count(), 0);
+        assert_eq!(qt.depth(), 0, "Emptied tree should collapse");
+    }
+
+    #[test]
+    fn remove_item_by_value() {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Tagged {
+            position: P2,
+            tag: &'static str,
+        }
+
+        impl Point for Tagged {
+            fn point(&self) -> P2 {
+                self.position
+            }
+        }
+
+        let a = Tagged {
+            position: point![25.0, 25.0],
+            tag: "a",
+        };
+        let b = Tagged {
+            position: point![25.0, 25.0],
+            tag: "b",
+        };
+        let c = Tagged {
+            position: point![75.0, 75.0],
+            tag: "c",
+        };
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 2);
+        qt.insert_many(&[a.clone(), b.clone(), c.clone()]);
+
+        assert!(qt.remove_item(&b), "Should remove the matching item");
+        assert_eq!(qt.count(), 2);
         assert_eq!(
-            results.len(),
-            2,
-            "Should pop two points that intersect with the shape"
+            qt.query(&make_circle(25.0, 25.0, 1.0)),
+            vec![a.clone()],
+            "The other item at the same position should be intact"
         );
-        assert!(
-            results.contains(&points[1]),
-            "Should contain point (20, 20)"
+        assert!(!qt.remove_item(&b), "Item should already be removed");
+        assert!(!qt.remove_item(&Tagged {
+            position: point![75.0, 75.0],
+            tag: "a",
+        }));
+        assert_eq!(qt.count(), 2);
+
+        assert!(qt.remove_item(&a));
+        assert!(qt.remove_item(&c));
+        assert_eq!(qt.count(), 0);
+        assert_eq!(qt.depth(), 0);
+    }
+
+    #[test]
+    fn clamp_region_to_boundary() {
+        let qt = QuadTree::<P2>::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        assert_eq!(
+            qt.clamp_region(make_rect(50.0, -20.0, 150.0, 40.0)),
+            Some(make_rect(50.0, 0.0, 100.0, 40.0)),
+            "Region half outside of the boundary should be clipped"
         );
-        assert!(
-            results.contains(&points[2]),
-            "Should contain point (30, 30)"
+        assert_eq!(
+            qt.clamp_region(make_rect(10.0, 10.0, 20.0, 20.0)),
+            Some(make_rect(10.0, 10.0, 20.0, 20.0))
+        );
+        assert_eq!(
+            qt.clamp_region(make_rect(-50.0, -50.0, 150.0, 150.0)),
+            Some(qt.boundary())
         );
+        assert_eq!(qt.clamp_region(make_rect(120.0, 10.0, 150.0, 20.0)), None);
+    }
 
-        let remaining_points = qt.query(&make_rect(0.0, 0.0, 100.0, 100.0));
-        assert_eq!(qt.count(), 2, "Two points should remain in the quadtree");
-        assert!(
-            remaining_points.contains(&points[0]),
-            "Should still contain point (10, 10)"
+    #[test]
+    fn get_approx_within_tolerance() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
+        qt.insert_many(&[point![10.0, 10.0], point![50.0, 50.0], point![80.0, 20.0]]);
+
+        assert_eq!(
+            qt.get(&point![10.0000001, 10.0]),
+            None,
+            "Exact lookup should miss a nearby position"
         );
-        assert!(
-            remaining_points.contains(&points[3]),
-            "Should still contain point (40, 40)"
+        assert_eq!(
+            qt.get_approx(&point![10.0000001, 10.0], 1e-6),
+            Some(point![10.0, 10.0]),
+            "Item within the tolerance should be found"
+        );
+        assert_eq!(
+            qt.get_approx(&point![10.1, 10.0], 1e-6),
+            None,
+            "Item beyond the tolerance should not be found"
+        );
+        assert_eq!(
+            qt.get_approx(&point![49.9999999, 49.9999999], 1e-6),
+            Some(point![50.0, 50.0]),
+            "Item across a cell border should be found"
         );
+        for tolerance in [-1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                qt.get_approx(&point![10.0, 10.0], tolerance),
+                None,
+                "Invalid tolerance should find nothing"
+            );
+        }
     }
 
     #[test]
-    fn pop_filter_exclude_point() {
+    fn walk_visits_every_node() {
+        #[derive(Debug, PartialEq)]
+        enum Visit {
+            Internal(Rect, usize),
+            Leaf(Rect, usize, Vec<P2>),
+        }
+
+        struct Recorder(Vec<Visit>);
+
+        impl NodeVisitor<P2> for Recorder {
+            fn visit_internal(&mut self, boundary: Rect, depth: usize) {
+                self.0.push(Visit::Internal(boundary, depth));
+            }
+
+            fn visit_leaf(&mut self, boundary: Rect, depth: usize, items: &[P2]) {
+                self.0.push(Visit::Leaf(boundary, depth, items.to_vec()));
+            }
+        }
+
         let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let points = vec![point![15.0, 15.0], point![20.0, 20.0], point![25.0, 25.0]];
-        qt.insert_many(&points);
+        qt.insert_many(&[point![10.0, 10.0], point![30.0, 30.0], point![60.0, 60.0]]);
 
-        let area = make_rect(10.0, 10.0, 30.0, 30.0);
-        let results = qt.pop_filter(&area, |p| p.point() != area.center());
-        assert_eq!(results.len(), 2, "Two items were popped");
-        assert_eq!(qt.count(), 1, "One item remains in tree");
-        assert!(
-            results.contains(&points[2]),
-            "Point at (25.0, 25.0) should have been popped"
-        );
-        assert!(
-            !results.contains(&points[1]),
-            "Point at (20.0, 20.0) should not have been popped"
+        let mut recorder = Recorder(vec![]);
+        qt.walk(&mut recorder);
+        assert_eq!(
+            recorder.0,
+            vec![
+                Visit::Internal(make_rect(0.0, 0.0, 100.0, 100.0), 0),
+                Visit::Internal(make_rect(0.0, 0.0, 50.0, 50.0), 1),
+                Visit::Leaf(make_rect(0.0, 0.0, 25.0, 25.0), 2, vec![point![10.0, 10.0]]),
+                Visit::Leaf(make_rect(25.0, 0.0, 50.0, 25.0), 2, vec![]),
+                Visit::Leaf(make_rect(0.0, 25.0, 25.0, 50.0), 2, vec![]),
+                Visit::Leaf(
+                    make_rect(25.0, 25.0, 50.0, 50.0),
+                    2,
+                    vec![point![30.0, 30.0]]
+                ),
+                Visit::Leaf(make_rect(50.0, 0.0, 100.0, 50.0), 1, vec![]),
+                Visit::Leaf(make_rect(0.0, 50.0, 50.0, 100.0), 1, vec![]),
+                Visit::Leaf(
+                    make_rect(50.0, 50.0, 100.0, 100.0),
+                    1,
+                    vec![point![60.0, 60.0]]
+                ),
+            ]
         );
+    }
+
+    #[test]
+    fn query_ordered_visit_near_to_far() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.insert_many(&scattered_points(300));
+        let region = make_rect(10.0, 10.0, 80.0, 60.0);
+        let origin = point![70.0, 20.0];
+
+        let mut visited = vec![];
+        qt.query_ordered_visit(&region, origin, |p| {
+            visited.push(*p);
+            ControlFlow::Continue(())
+        });
+        let mut expected = qt.query(&region);
+        assert_eq!(visited.len(), expected.len());
         assert!(
-            qt.get(&points[1]).is_some(),
-            "Point at (20.0, 20.0) should still exist"
+            visited
+                .windows(2)
+                .all(|w| na::distance(&w[0], &origin) <= na::distance(&w[1], &origin)),
+            "Items should be visited in non-decreasing distance"
         );
+        visited.sort_by(cmp_xy);
+        expected.sort_by(cmp_xy);
+        assert_eq!(visited, expected);
+
+        let mut count = 0;
+        qt.query_ordered_visit(&region, origin, |_| {
+            count += 1;
+            if count == 5 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(count, 5, "Break should stop further visits");
     }
 
     #[test]
-    fn precise_floating_point_handling() {
-        let mut qt = QuadTree::new(make_rect(0.00001, 0.00001, 99.99999, 99.99999), 2);
-        let point = point![0.0001, 0.0001];
+    fn query_instrumented_counts_pruning() {
+        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 4);
+        qt.insert_many(&scattered_points(1000));
+
+        let (all, full) = qt.query_instrumented(&qt.boundary());
+        assert_eq!(all.len(), 1000);
+        assert_eq!(full.nodes_visited, qt.node_boundaries().len());
+        assert_eq!(full.items_examined, 1000);
+
+        let region = make_rect(40.0, 40.0, 42.0, 42.0);
+        let (found, tiny) = qt.query_instrumented(&region);
+        assert_eq!(found, qt.query(&region));
         assert!(
-            qt.insert(&point),
-            "Point near the boundary should be inserted."
+            tiny.nodes_visited * 10 < full.nodes_visited,
+            "Tiny query visited {} nodes, full query visited {}",
+            tiny.nodes_visited,
+            full.nodes_visited
         );
+        assert!(tiny.leaves_visited < tiny.nodes_visited);
+        assert!(tiny.items_examined < 50);
     }
 
-    #[cfg(feature = "serde")]
-    #[test]
-    fn test_quadtree_serialization() {
-        let mut qt = QuadTree::new(make_rect(0.0, 0.0, 100.0, 100.0), 1);
-        let points = vec![point![10.0, 10.0], point![20.0, 20.0], point![30.0, 30.0]];
-        qt.insert_many(&points);
+    /// Deterministic scattered points for comparing tree variants
+    fn scattered_points(n: usize) -> Vec<P2> {
+        let mut state = 0x2545_f491_u64;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64 * 100.0
+        };
+        (0..n).map(|_| point![next(), next()]).collect()
+    }
 
-        let serialized = serde_json::to_string(&qt).expect("Failed to serialize QuadTree");
-        let expected_json = r#"[[10.0,10.0],[20.0,20.0],[30.0,30.0]]"#;
+    #[test]
+    fn fixed_capacity_matches_runtime() {
+        let boundary = make_rect(0.0, 0.0, 100.0, 100.0);
+        let mut runtime = QuadTree::new(boundary, 4);
+        let mut fixed = FixedQuadTree::<P2, 4>::new_fixed(boundary);
 
+        let points = scattered_points(500);
+        for point in &points[..250] {
+            assert_eq!(runtime.insert(point), fixed.insert(point));
+        }
         assert_eq!(
-            serialized, expected_json,
-            "Serialized QuadTree does not match expected JSON output"
+            runtime.insert_many(&points[250..]),
+            fixed.insert_many(&points[250..])
+        );
+        assert_eq!(
+            runtime.node_boundaries(),
+            fixed.node_boundaries(),
+            "Both trees should subdivide identically"
         );
+
+        let region = make_circle(40.0, 60.0, 25.0);
+        assert_eq!(runtime.query(&region), fixed.query(&region));
+        assert_eq!(runtime.delete(&region), fixed.delete(&region));
+        assert_eq!(runtime.node_boundaries(), fixed.node_boundaries());
+        assert_eq!(runtime.count(), fixed.count());
+        assert_eq!(fixed.validate(), Ok(()));
     }
 }
@@ -1,13 +1,22 @@
-use nalgebra::{self as na, vector};
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+
+use nalgebra::{self as na, vector, Vector2};
 
 #[cfg(feature = "serde")]
 use serde::Serialize;
 
-use crate::{Point, P2};
+use crate::{
+    util::{distance_squared_to_rect, is_finite},
+    Point, P2,
+};
 
 /// A trait for shapes that can be used to query the QuadTree. Shapes must be able to
 /// provide their start and end points, their center point, and check if they contain
 /// a point. They must also be able to check if they intersect with another shape.
+///
+/// The trait is object safe, so a shape chosen at runtime can be used as a `&dyn Shape`. Checking
+/// intersection with another shape of the same type is only available on concrete shapes.
 pub trait Shape {
     /// Get the start point of the shape
     fn start(&self) -> P2;
@@ -18,17 +27,61 @@ pub trait Shape {
     /// Check if the shape contains a point
     fn contains(&self, point: &P2) -> bool;
     /// Check if the shape shares any space with another shape
-    fn intersects(&self, other: &Self) -> bool;
+    fn intersects(&self, other: &Self) -> bool
+    where
+        Self: Sized;
 
     /// Get the bounding rect of the shape
     fn rect(&self) -> Rect {
         Rect::new(self.start(), self.end())
     }
 
+    /// Get a circle containing the shape
+    ///
+    /// Defaults to the circle through the corners of the bounding rect, so shapes that are
+    /// circular should override this.
+    fn bounding_circle(&self) -> Circle {
+        let (start, end) = (self.start(), self.end());
+        Circle::new(na::center(&start, &end), na::distance(&start, &end) / 2.0)
+    }
+
     /// Check if the shape fully contains a given rect
     fn contains_rect(&self, rect: &Rect) -> bool {
         self.contains(&rect.start()) && self.contains(&rect.end())
     }
+
+    /// Check if the shape shares any space with a given rect
+    fn intersects_rect(&self, rect: &Rect) -> bool {
+        rect.intersects(&self.rect())
+    }
+
+    /// Get the area of the shape
+    ///
+    /// Defaults to the area of the bounding rect, so shapes that don't fill their bounding rect
+    /// should override this.
+    fn area(&self) -> f64 {
+        let size = self.end() - self.start();
+        size.x * size.y
+    }
+
+    /// Get the perimeter of the shape
+    ///
+    /// Defaults to the perimeter of the bounding rect, so shapes that don't fill their bounding
+    /// rect should override this.
+    fn perimeter(&self) -> f64 {
+        let size = self.end() - self.start();
+        2.0 * (size.x + size.y)
+    }
+
+    /// Get the signed distance from a point to the edge of the shape, which is negative inside
+    /// the shape and positive outside of it
+    ///
+    /// Defaults to the signed distance to the bounding rect, so shapes that don't fill their
+    /// bounding rect should override this. Every shape in this module does, and the distance is
+    /// exact for all of them except inside the bends of a [`Polyline`].
+    fn distance_to(&self, point: &P2) -> f64 {
+        self.rect().distance_to(point)
+    }
 }
 
 impl<T: Point> Shape for T {
@@ -75,6 +128,40 @@ impl Rect {
         }
     }
 
+    /// Create a new rect with a center point and half of its width and height
+    pub fn from_center(center: P2, half_width: f64, half_height: f64) -> Self {
+        let v = vector![half_width, half_height];
+        Self::new(center - v, center + v)
+    }
+
+    /// Create a new rect with a start point and its width and height
+    pub fn from_size(min: P2, width: f64, height: f64) -> Self {
+        Self::new(min, min + vector![width, height])
+    }
+
+    /// Get the width of the rect, which is negative if its end is left of its start
+    pub fn width(&self) -> f64 {
+        self.end.x - self.start.x
+    }
+
+    /// Get the height of the rect, which is negative if its end is below its start
+    pub fn height(&self) -> f64 {
+        self.end.y - self.start.y
+    }
+
+    /// Check if the rect has no area, meaning its width or height is zero, negative, or `NaN`
+    pub fn is_degenerate(&self) -> bool {
+        !(self.width() > 0.0 && self.height() > 0.0)
+    }
+
+    /// Check if the rect contains a point, excluding its right and top edges
+    ///
+    /// Unlike [`Shape::contains`], which includes every edge, this is half-open on each axis
+    /// (`start <= p < end`), so rects that tile a region share no points.
+    pub fn contains_exclusive(&self, point: &P2) -> bool {
+        *point >= self.start && point.x < self.end.x && point.y < self.end.y
+    }
+
     /// Set the start point of the rect
     pub fn set_start(&mut self, start: P2) {
         self.start = start;
@@ -87,6 +174,27 @@ impl Rect {
         self.center = na::center(&self.start, &self.end);
     }
 
+    /// Get the four corners of the rect
+    pub fn corners(&self) -> [P2; 4] {
+        [
+            self.start,
+            P2::new(self.end.x, self.start.y),
+            P2::new(self.start.x, self.end.y),
+            self.end,
+        ]
+    }
+
+    /// Get a copy of the rect moved by an offset
+    pub fn translate(&self, offset: Vector2<f64>) -> Self {
+        Self::new(self.start + offset, self.end + offset)
+    }
+
+    /// Get a copy of the rect scaled about its center by a non-negative factor
+    pub fn scale(&self, factor: f64) -> Self {
+        let half = (self.end - self.start) / 2.0 * factor;
+        Self::new(self.center - half, self.center + half)
+    }
+
     /// Quarter the rect to produce four smaller rects
     pub fn quarter(&self) -> [Self; 4] {
         let &Rect { start, center, end } = self;
@@ -130,15 +238,51 @@ impl Shape for Rect {
     fn rect(&self) -> Rect {
         *self
     }
+
+    fn distance_to(&self, point: &P2) -> f64 {
+        distance_to_box(&(point - self.center), &((self.end - self.start) / 2.0))
+    }
+}
+
+// Signed distance from an offset from the center of an axis-aligned box to its edge
+fn distance_to_box(offset: &Vector2<f64>, half_extents: &Vector2<f64>) -> f64 {
+    let d = offset.abs() - half_extents;
+    let outside = d.sup(&Vector2::zeros()).norm();
+    let inside = d.x.max(d.y).min(0.0);
+    outside + inside
+}
+
+/// Create a rect from its start and end points
+impl From<(P2, P2)> for Rect {
+    fn from((start, end): (P2, P2)) -> Self {
+        Self::new(start, end)
+    }
+}
+
+/// Create a rect from its coordinates in the order `[start.x, start.y, end.x, end.y]`
+impl From<[f64; 4]> for Rect {
+    fn from([x1, y1, x2, y2]: [f64; 4]) -> Self {
+        Self::new(P2::new(x1, y1), P2::new(x2, y2))
+    }
 }
 
 /// Represents a circle defined by a center point and radius. Provides utility functions
 /// for geometric calculations, particularly for interactions with QuadTree.
+///
+/// The squared radius is cached so that containment checks compare squared distances,
+/// avoiding a square root per point in large circular queries.
+///
+/// Circles are closed by default, so points exactly on the perimeter are contained. Use
+/// [`Circle::new_exclusive`] or [`Circle::set_inclusive`] for an open disk.
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Circle {
     center: P2,
     radius: f64,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "is_true"))]
+    inclusive: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    radius_squared: f64,
     #[cfg_attr(feature = "serde", serde(skip))]
     start: P2,
     #[cfg_attr(feature = "serde", serde(skip))]
@@ -154,13 +298,34 @@ impl Circle {
         Self {
             center,
             radius,
+            inclusive: true,
+            radius_squared: radius * radius,
             start,
             end,
         }
     }
 
+    /// Create a new circle that excludes points exactly on its perimeter
+    pub fn new_exclusive(center: P2, radius: f64) -> Self {
+        Self {
+            inclusive: false,
+            ..Self::new(center, radius)
+        }
+    }
+
+    /// Create a new circle, rejecting a negative radius or non-finite values
+    ///
+    /// **Returns** `None` if the circle would be invalid
+    pub fn try_new(center: P2, radius: f64) -> Option<Self> {
+        if radius < 0.0 || !radius.is_finite() || !is_finite(&center) {
+            return None;
+        }
+        Some(Self::new(center, radius))
+    }
+
     fn update_bounds(&mut self) {
         let v = vector![self.radius, self.radius];
+        self.radius_squared = self.radius * self.radius;
         self.start = self.center - v;
         self.end = self.center + v;
     }
@@ -176,6 +341,30 @@ impl Circle {
         self.radius = radius;
         self.update_bounds();
     }
+
+    /// Set whether points exactly on the perimeter are contained in the circle
+    pub fn set_inclusive(&mut self, inclusive: bool) {
+        self.inclusive = inclusive;
+    }
+
+    /// Check if points exactly on the perimeter are contained in the circle
+    pub fn is_inclusive(&self) -> bool {
+        self.inclusive
+    }
+
+    /// Get a copy of the circle moved by an offset
+    pub fn translate(&self, offset: Vector2<f64>) -> Self {
+        let mut circle = *self;
+        circle.set_center(self.center + offset);
+        circle
+    }
+
+    /// Get a copy of the circle with its radius scaled by a non-negative factor
+    pub fn scale(&self, factor: f64) -> Self {
+        let mut circle = *self;
+        circle.set_radius(self.radius * factor);
+        circle
+    }
 }
 
 impl Shape for Circle {
@@ -192,210 +381,1167 @@ impl Shape for Circle {
     }
 
     fn contains(&self, point: &P2) -> bool {
-        na::distance(&self.center, point) <= self.radius
+        // The cached squared radius hides the sign, so a negative radius is checked here
+        if self.radius < 0.0 {
+            return false;
+        }
+        let distance_squared = na::distance_squared(&self.center, point);
+        if self.inclusive {
+            distance_squared <= self.radius_squared
+        } else {
+            distance_squared < self.radius_squared
+        }
     }
 
     fn intersects(&self, other: &Self) -> bool {
-        na::distance(&self.center, &other.center) <= self.radius + other.radius
+        let radii = self.radius + other.radius;
+        na::distance_squared(&self.center, &other.center) <= radii * radii
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::util::tests::{make_circle, make_rect};
-    use nalgebra::point;
+    fn bounding_circle(&self) -> Circle {
+        *self
+    }
 
-    use super::*;
+    fn intersects_rect(&self, rect: &Rect) -> bool {
+        if self.radius < 0.0 {
+            return false;
+        }
+        distance_squared_to_rect(rect, &self.center) <= self.radius_squared
+    }
 
-    #[test]
-    fn rect_properties() {
-        let rect = make_rect(0.0, 0.0, 10.0, 10.0);
-        assert_eq!(
-            rect.start(),
-            point![0.0, 0.0],
-            "Start should be at (0.0, 0.0)"
-        );
-        assert_eq!(
-            rect.end(),
-            point![10.0, 10.0],
-            "End should be at (10.0, 10.0)"
-        );
-        assert_eq!(
-            rect.center(),
-            point![5.0, 5.0],
-            "Center should be at (5.0, 5.0)"
-        );
+    fn area(&self) -> f64 {
+        PI * self.radius_squared
     }
 
-    #[test]
-    fn rect_contains_point() {
-        let rect = make_rect(0.0, 0.0, 10.0, 10.0);
-        assert!(
-            rect.contains(&point![5.0, 5.0]),
-            "Rect should contain point (5.0, 5.0)"
-        );
-        assert!(
-            !rect.contains(&point![-1.0, 5.0]),
-            "Rect should not contain point (-1.0, 5.0)"
-        );
-        assert!(
-            rect.contains(&point![0.0, 0.0]),
-            "Rect should contain its start point (0.0, 0.0)"
-        );
-        assert!(
-            rect.contains(&point![10.0, 10.0]),
-            "Rect should contain its end point (10.0, 10.0)"
-        );
+    fn perimeter(&self) -> f64 {
+        2.0 * PI * self.radius
     }
 
-    #[test]
-    fn rect_intersects_with_another_rect() {
-        let rect1 = make_rect(0.0, 0.0, 10.0, 10.0);
-        let rect2 = make_rect(5.0, 5.0, 15.0, 15.0);
-        assert!(
-            rect1.intersects(&rect2),
-            "Rect1 should intersect with Rect2"
-        );
+    fn distance_to(&self, point: &P2) -> f64 {
+        na::distance(&self.center, point) - self.radius
+    }
+}
 
-        let rect3 = make_rect(10.0, 10.0, 20.0, 20.0);
-        assert!(
-            rect1.intersects(&rect3),
-            "Rect1 should touch Rect3 at the edge, counting as intersect"
-        );
+#[cfg(feature = "serde")]
+fn is_true(value: &bool) -> bool {
+    *value
+}
 
-        let rect4 = make_rect(11.0, 11.0, 21.0, 21.0);
-        assert!(
-            !rect1.intersects(&rect4),
-            "Rect1 should not intersect with Rect4"
-        );
+/// Represents the half of the plane on one side of a line, defined by a point on the line and
+/// a normal vector pointing into the half-plane. Useful for directional culling, such as
+/// selecting everything in front of a camera plane.
+///
+/// A half-plane is unbounded, so its bounding rect spans the entire range of `f64`. Queries
+/// rely on [`Shape::intersects_rect`] and [`Shape::contains_rect`] for pruning instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct HalfPlane {
+    origin: P2,
+    normal: Vector2<f64>,
+}
 
-        let rect5 = make_rect(-5.0, -5.0, -1.0, -1.0);
-        assert!(
-            !rect1.intersects(&rect5),
-            "Rect1 should not intersect with Rect5"
-        );
+impl HalfPlane {
+    /// Create a new half-plane with a point on its edge and a normal vector pointing into it
+    pub fn new(origin: P2, normal: Vector2<f64>) -> Self {
+        Self { origin, normal }
+    }
 
-        let rect6 = make_rect(3.0, 3.0, 7.0, 7.0);
-        assert!(
-            rect1.intersects(&rect6),
-            "Rect6 is entirely inside Rect1, should intersect"
-        );
+    /// Set the point on the edge of the half-plane
+    pub fn set_origin(&mut self, origin: P2) {
+        self.origin = origin;
+    }
 
-        let rect7 = make_rect(-10.0, 0.0, -1.0, 10.0);
-        assert!(
-            !rect1.intersects(&rect7),
-            "Rect1 should not intersect with Rect7 on the left"
-        );
+    /// Set the normal vector of the half-plane
+    pub fn set_normal(&mut self, normal: Vector2<f64>) {
+        self.normal = normal;
+    }
+}
 
-        let rect8 = make_rect(0.0, -10.0, 10.0, -1.0);
-        assert!(
-            !rect1.intersects(&rect8),
-            "Rect1 should not intersect with Rect8 below it"
-        );
+impl Shape for HalfPlane {
+    fn start(&self) -> P2 {
+        P2::new(f64::MIN, f64::MIN)
+    }
 
-        let rect9 = make_rect(0.0, 10.0, 10.0, 20.0);
-        assert!(
-            rect1.intersects(&rect9),
-            "Rect1 should touch Rect9 at the top, counting as intersect"
-        );
+    fn end(&self) -> P2 {
+        P2::new(f64::MAX, f64::MAX)
+    }
 
-        let rect10 = make_rect(10.0, 0.0, 20.0, 10.0);
-        assert!(
-            rect1.intersects(&rect10),
-            "Rect1 should touch Rect10 on the right, counting as intersect"
-        );
+    fn center(&self) -> P2 {
+        self.origin
+    }
 
-        let rect11 = make_rect(5.0, -5.0, 15.0, 5.0);
-        assert!(
-            rect1.intersects(&rect11),
-            "Rect11 should intersect with the bottom part of Rect1"
-        );
+    fn contains(&self, point: &P2) -> bool {
+        (point - self.origin).dot(&self.normal) >= 0.0
     }
 
-    #[test]
-    fn rect_contains_another_rect() {
-        let outer_rect = make_rect(0.0, 0.0, 10.0, 10.0);
-        let inner_rect = make_rect(1.0, 1.0, 9.0, 9.0);
-        assert!(
-            outer_rect.contains_rect(&inner_rect),
-            "Outer rect should contain inner rect completely"
-        );
+    fn intersects(&self, other: &Self) -> bool {
+        // Only half-planes facing in exactly opposite directions can be disjoint
+        let anti_parallel =
+            self.normal.perp(&other.normal) == 0.0 && self.normal.dot(&other.normal) < 0.0;
+        !anti_parallel || self.contains(&other.origin)
+    }
 
-        let outer_rect = make_rect(0.0, 0.0, 10.0, 10.0);
-        let overlapping_rect = make_rect(5.0, 5.0, 15.0, 15.0);
-        assert!(
-            !outer_rect.contains_rect(&overlapping_rect),
-            "Outer rect should not contain overlapping rect"
-        );
+    fn contains_rect(&self, rect: &Rect) -> bool {
+        rect.corners().iter().all(|corner| self.contains(corner))
     }
 
-    #[test]
-    fn quartering_rect() {
-        let rect = make_rect(0.0, 0.0, 10.0, 10.0);
-        let quarters = rect.quarter();
-        // Ensure each quarter is correctly sized and positioned
-        assert_eq!(
-            quarters[0],
-            make_rect(0.0, 0.0, 5.0, 5.0),
-            "Top-left quarter should match expected dimensions"
-        );
-        assert_eq!(
-            quarters[1],
-            make_rect(5.0, 0.0, 10.0, 5.0),
-            "Top-right quarter should match expected dimensions"
-        );
-        assert_eq!(
-            quarters[2],
-            make_rect(0.0, 5.0, 5.0, 10.0),
-            "Bottom-left quarter should match expected dimensions"
-        );
-        assert_eq!(
-            quarters[3],
-            make_rect(5.0, 5.0, 10.0, 10.0),
-            "Bottom-right quarter should match expected dimensions"
-        );
+    fn intersects_rect(&self, rect: &Rect) -> bool {
+        rect.corners().iter().any(|corner| self.contains(corner))
     }
 
-    #[test]
-    fn circle_properties_and_bounds() {
-        let circle = make_circle(5.0, 5.0, 5.0);
-        assert_eq!(
-            circle.center(),
-            point![5.0, 5.0],
-            "Center should be at (5.0, 5.0)"
-        );
-        assert_eq!(circle.radius, 5.0, "Radius should be 5.0");
-        assert_eq!(
-            circle.start(),
-            point![0.0, 0.0],
-            "Start should be at (0.0, 0.0)"
-        );
-        assert_eq!(
-            circle.end(),
-            point![10.0, 10.0],
-            "End should be at (10.0, 10.0)"
-        );
+    /// A half-plane is unbounded, so its area is infinite
+    fn area(&self) -> f64 {
+        f64::INFINITY
     }
 
-    #[test]
-    fn circle_contains_point() {
-        let circle = make_circle(5.0, 5.0, 5.0);
-        assert!(
-            circle.contains(&point![5.0, 5.0]),
-            "Circle should contain its center point"
-        );
-        assert!(
-            circle.contains(&point![0.0, 5.0]),
-            "Circle should contain point on its perimeter"
-        );
-        assert!(
-            !circle.contains(&point![0.0, 0.0]),
-            "Circle should not contain points outside its boundary"
-        );
+    /// A half-plane is unbounded, so its perimeter is infinite
+    fn perimeter(&self) -> f64 {
+        f64::INFINITY
     }
 
-    #[test]
-    fn circle_intersects_another_circle() {
+    fn distance_to(&self, point: &P2) -> f64 {
+        -(point - self.origin).dot(&self.normal) / self.normal.norm()
+    }
+}
+
+/// Represents a rectangle rotated about its center, defined by a center point, half-extents
+/// along its local axes, and a rotation angle in radians. Its bounding rect is the axis-aligned
+/// box around its four rotated corners.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct OrientedRect {
+    center: P2,
+    half_extents: Vector2<f64>,
+    angle: f64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    rotation: na::Rotation2<f64>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    start: P2,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    end: P2,
+}
+
+impl OrientedRect {
+    /// Create a new oriented rect with a center point, half-extents, and rotation angle in radians
+    pub fn new(center: P2, half_extents: Vector2<f64>, angle: f64) -> Self {
+        let mut rect = Self {
+            center,
+            half_extents,
+            angle,
+            rotation: na::Rotation2::new(angle),
+            start: center,
+            end: center,
+        };
+        rect.update_bounds();
+        rect
+    }
+
+    fn update_bounds(&mut self) {
+        self.rotation = na::Rotation2::new(self.angle);
+        let corners = self.corners();
+        self.start = corners[0];
+        self.end = corners[0];
+        for corner in &corners[1..] {
+            self.start = self.start.inf(corner);
+            self.end = self.end.sup(corner);
+        }
+    }
+
+    /// Get the four rotated corners of the rect
+    pub fn corners(&self) -> [P2; 4] {
+        let (hx, hy) = (self.half_extents.x, self.half_extents.y);
+        [
+            vector![-hx, -hy],
+            vector![hx, -hy],
+            vector![hx, hy],
+            vector![-hx, hy],
+        ]
+        .map(|v| self.center + self.rotation * v)
+    }
+
+    /// Set the center point of the rect
+    pub fn set_center(&mut self, center: P2) {
+        self.center = center;
+        self.update_bounds();
+    }
+
+    /// Set the half-extents of the rect along its local axes
+    pub fn set_half_extents(&mut self, half_extents: Vector2<f64>) {
+        self.half_extents = half_extents;
+        self.update_bounds();
+    }
+
+    /// Set the rotation angle of the rect in radians
+    pub fn set_angle(&mut self, angle: f64) {
+        self.angle = angle;
+        self.update_bounds();
+    }
+
+    fn axes(&self) -> [Vector2<f64>; 2] {
+        [self.rotation * Vector2::x(), self.rotation * Vector2::y()]
+    }
+}
+
+// Separating axis test between two convex polygons, given the candidate separating axes
+fn separated_on_any_axis(a: &[P2], b: &[P2], axes: &[Vector2<f64>]) -> bool {
+    let project = |corners: &[P2], axis: &Vector2<f64>| {
+        corners
+            .iter()
+            .map(|c| c.coords.dot(axis))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), d| {
+                (min.min(d), max.max(d))
+            })
+    };
+    axes.iter().any(|axis| {
+        let (a_min, a_max) = project(a, axis);
+        let (b_min, b_max) = project(b, axis);
+        a_max < b_min || b_max < a_min
+    })
+}
+
+impl Shape for OrientedRect {
+    fn start(&self) -> P2 {
+        self.start
+    }
+
+    fn end(&self) -> P2 {
+        self.end
+    }
+
+    fn center(&self) -> P2 {
+        self.center
+    }
+
+    fn contains(&self, point: &P2) -> bool {
+        let local = self
+            .rotation
+            .inverse_transform_vector(&(point - self.center));
+        local.x.abs() <= self.half_extents.x && local.y.abs() <= self.half_extents.y
+    }
+
+    fn intersects(&self, other: &Self) -> bool {
+        let [a1, a2] = self.axes();
+        let [b1, b2] = other.axes();
+        !separated_on_any_axis(&self.corners(), &other.corners(), &[a1, a2, b1, b2])
+    }
+
+    fn contains_rect(&self, rect: &Rect) -> bool {
+        rect.corners().iter().all(|corner| self.contains(corner))
+    }
+
+    fn intersects_rect(&self, rect: &Rect) -> bool {
+        let [a1, a2] = self.axes();
+        let axes = [a1, a2, Vector2::x(), Vector2::y()];
+        !separated_on_any_axis(&self.corners(), &rect.corners(), &axes)
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * self.half_extents.x * self.half_extents.y
+    }
+
+    fn perimeter(&self) -> f64 {
+        4.0 * (self.half_extents.x + self.half_extents.y)
+    }
+
+    fn distance_to(&self, point: &P2) -> f64 {
+        let local = self
+            .rotation
+            .inverse_transform_vector(&(point - self.center));
+        distance_to_box(&local, &self.half_extents)
+    }
+}
+
+/// Represents a circular sector (pie slice) defined by a center point, radius, facing angle, and
+/// half-angle, all angles in radians. Contains the points within `radius` of the center whose
+/// direction from the center is within `half_angle` of the facing direction. Useful for
+/// field-of-view queries.
+///
+/// The bounding rect is that of the full circle, which is conservative but keeps pruning cheap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Sector {
+    center: P2,
+    radius: f64,
+    facing: f64,
+    half_angle: f64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    direction: Vector2<f64>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cos_half_angle: f64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    start: P2,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    end: P2,
+}
+
+impl Sector {
+    /// Create a new sector with a center point, radius, facing angle, and half-angle
+    pub fn new(center: P2, radius: f64, facing: f64, half_angle: f64) -> Self {
+        let mut sector = Self {
+            center,
+            radius,
+            facing,
+            half_angle,
+            direction: Vector2::x(),
+            cos_half_angle: 1.0,
+            start: center,
+            end: center,
+        };
+        sector.update_bounds();
+        sector
+    }
+
+    fn update_bounds(&mut self) {
+        let facing = na::UnitComplex::new(self.facing);
+        self.direction = vector![facing.re, facing.im];
+        self.cos_half_angle = na::UnitComplex::new(self.half_angle).re;
+        let v = vector![self.radius, self.radius];
+        self.start = self.center - v;
+        self.end = self.center + v;
+    }
+
+    /// Set the center point of the sector
+    pub fn set_center(&mut self, center: P2) {
+        self.center = center;
+        self.update_bounds();
+    }
+
+    /// Set the radius of the sector
+    pub fn set_radius(&mut self, radius: f64) {
+        self.radius = radius;
+        self.update_bounds();
+    }
+
+    /// Set the facing angle of the sector
+    pub fn set_facing(&mut self, facing: f64) {
+        self.facing = facing;
+        self.update_bounds();
+    }
+
+    /// Set the half-angle of the sector
+    pub fn set_half_angle(&mut self, half_angle: f64) {
+        self.half_angle = half_angle;
+        self.update_bounds();
+    }
+}
+
+impl Shape for Sector {
+    fn start(&self) -> P2 {
+        self.start
+    }
+
+    fn end(&self) -> P2 {
+        self.end
+    }
+
+    fn center(&self) -> P2 {
+        self.center
+    }
+
+    fn contains(&self, point: &P2) -> bool {
+        // Comparing the cosine of the angle to the facing direction avoids any wraparound at ±π
+        let v = point - self.center;
+        let distance = v.norm();
+        distance <= self.radius && v.dot(&self.direction) >= distance * self.cos_half_angle
+    }
+
+    /// Conservatively check intersection using the sectors' full circles
+    fn intersects(&self, other: &Self) -> bool {
+        na::distance(&self.center, &other.center) <= self.radius + other.radius
+    }
+
+    fn contains_rect(&self, rect: &Rect) -> bool {
+        // Containing every corner only implies containing the rect if the sector is convex
+        self.half_angle <= core::f64::consts::FRAC_PI_2
+            && rect.corners().iter().all(|corner| self.contains(corner))
+    }
+
+    fn area(&self) -> f64 {
+        self.half_angle.min(PI) * self.radius * self.radius
+    }
+
+    fn perimeter(&self) -> f64 {
+        if self.half_angle >= PI {
+            return 2.0 * PI * self.radius;
+        }
+        2.0 * self.radius * (1.0 + self.half_angle)
+    }
+
+    fn distance_to(&self, point: &P2) -> f64 {
+        let v = point - self.center;
+        let arc = v.norm() - self.radius;
+        if self.half_angle >= PI {
+            return arc;
+        }
+
+        // In the frame facing along the x axis, mirrored so the point is on the upper edge's side
+        let local = vector![v.dot(&self.direction), self.direction.perp(&v).abs()];
+        let edge = na::UnitComplex::new(self.half_angle);
+        let edge = vector![edge.re, edge.im];
+        let closest = edge * local.dot(&edge).clamp(0.0, self.radius);
+        let to_edge = (local - closest).norm();
+        if edge.perp(&local) > 0.0 {
+            arc.max(to_edge)
+        } else {
+            arc.max(-to_edge)
+        }
+    }
+}
+
+/// Represents a triangle defined by three vertices, in either winding order. Contains the points
+/// inside or on the edges of the triangle. A degenerate (collinear) triangle contains nothing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Triangle {
+    vertices: [P2; 3],
+    #[cfg_attr(feature = "serde", serde(skip))]
+    start: P2,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    end: P2,
+}
+
+impl Triangle {
+    /// Create a new triangle from three vertices
+    pub fn new(a: P2, b: P2, c: P2) -> Self {
+        Self {
+            vertices: [a, b, c],
+            start: a.inf(&b).inf(&c),
+            end: a.sup(&b).sup(&c),
+        }
+    }
+
+    /// Get the vertices of the triangle
+    pub fn vertices(&self) -> [P2; 3] {
+        self.vertices
+    }
+
+    /// Set the vertices of the triangle
+    pub fn set_vertices(&mut self, a: P2, b: P2, c: P2) {
+        *self = Self::new(a, b, c);
+    }
+
+    /// Check if the vertices are collinear, in which case the triangle contains nothing
+    pub fn is_degenerate(&self) -> bool {
+        let [a, b, c] = self.vertices;
+        (b - a).perp(&(c - a)) == 0.0
+    }
+
+    fn axes(&self) -> [Vector2<f64>; 3] {
+        let [a, b, c] = self.vertices;
+        [b - a, c - b, a - c].map(|edge| vector![-edge.y, edge.x])
+    }
+}
+
+impl Shape for Triangle {
+    fn start(&self) -> P2 {
+        self.start
+    }
+
+    fn end(&self) -> P2 {
+        self.end
+    }
+
+    /// Get the centroid of the triangle
+    fn center(&self) -> P2 {
+        let [a, b, c] = self.vertices;
+        P2::from((a.coords + b.coords + c.coords) / 3.0)
+    }
+
+    fn contains(&self, point: &P2) -> bool {
+        if self.is_degenerate() {
+            return false;
+        }
+
+        // The point is inside if it is on the same side of every edge
+        let [a, b, c] = self.vertices;
+        let d1 = (b - a).perp(&(point - a));
+        let d2 = (c - b).perp(&(point - b));
+        let d3 = (a - c).perp(&(point - c));
+        let has_neg = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+        let has_pos = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+        !(has_neg && has_pos)
+    }
+
+    fn intersects(&self, other: &Self) -> bool {
+        if self.is_degenerate() || other.is_degenerate() {
+            return false;
+        }
+
+        let [a1, a2, a3] = self.axes();
+        let [b1, b2, b3] = other.axes();
+        !separated_on_any_axis(&self.vertices, &other.vertices, &[a1, a2, a3, b1, b2, b3])
+    }
+
+    fn contains_rect(&self, rect: &Rect) -> bool {
+        rect.corners().iter().all(|corner| self.contains(corner))
+    }
+
+    fn intersects_rect(&self, rect: &Rect) -> bool {
+        if self.is_degenerate() {
+            return false;
+        }
+
+        let [a1, a2, a3] = self.axes();
+        let axes = [a1, a2, a3, Vector2::x(), Vector2::y()];
+        !separated_on_any_axis(&self.vertices, &rect.corners(), &axes)
+    }
+
+    fn area(&self) -> f64 {
+        let [a, b, c] = self.vertices;
+        (b - a).perp(&(c - a)).abs() / 2.0
+    }
+
+    fn perimeter(&self) -> f64 {
+        let [a, b, c] = self.vertices;
+        na::distance(&a, &b) + na::distance(&b, &c) + na::distance(&c, &a)
+    }
+
+    fn distance_to(&self, point: &P2) -> f64 {
+        let [a, b, c] = self.vertices;
+        let distance = [(a, b), (b, c), (c, a)]
+            .iter()
+            .map(|(start, end)| na::distance(point, &closest_on_segment(point, start, end)))
+            .fold(f64::INFINITY, f64::min);
+        if self.contains(point) {
+            -distance
+        } else {
+            distance
+        }
+    }
+}
+
+/// Represents a corridor around a path of connected points, containing the points within
+/// `radius` of any segment of the path. A polyline with a single point behaves like a circle, and
+/// an empty polyline contains nothing. Useful for querying near routes.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Polyline {
+    points: Vec<P2>,
+    radius: f64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    start: P2,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    end: P2,
+}
+
+impl Polyline {
+    /// Create a new polyline from its points in path order and the radius of its corridor
+    pub fn new(points: Vec<P2>, radius: f64) -> Self {
+        let mut polyline = Self {
+            points,
+            radius,
+            start: P2::origin(),
+            end: P2::origin(),
+        };
+        polyline.update_bounds();
+        polyline
+    }
+
+    fn update_bounds(&mut self) {
+        let Some(first) = self.points.first() else {
+            return;
+        };
+        let v = vector![self.radius, self.radius];
+        let (start, end) = self.points[1..]
+            .iter()
+            .fold((*first, *first), |(start, end), p| {
+                (start.inf(p), end.sup(p))
+            });
+        self.start = start - v;
+        self.end = end + v;
+    }
+
+    /// Get the points of the path
+    pub fn points(&self) -> &[P2] {
+        &self.points
+    }
+
+    /// Set the points of the path
+    pub fn set_points(&mut self, points: Vec<P2>) {
+        self.points = points;
+        self.update_bounds();
+    }
+
+    /// Set the radius of the corridor
+    pub fn set_radius(&mut self, radius: f64) {
+        self.radius = radius;
+        self.update_bounds();
+    }
+
+    /// Get the length of the path
+    pub fn length(&self) -> f64 {
+        self.segments().map(|(a, b)| na::distance(&a, &b)).sum()
+    }
+
+    // A single point is treated as a zero-length segment
+    fn segments(&self) -> impl Iterator<Item = (P2, P2)> + '_ {
+        let single = (self.points.len() == 1).then(|| (self.points[0], self.points[0]));
+        self.points.windows(2).map(|w| (w[0], w[1])).chain(single)
+    }
+}
+
+fn closest_on_segment(point: &P2, a: &P2, b: &P2) -> P2 {
+    let ab = b - a;
+    let length_squared = ab.norm_squared();
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        ((point - a).dot(&ab) / length_squared).clamp(0.0, 1.0)
+    };
+    a + ab * t
+}
+
+fn distance_squared_to_segment(point: &P2, a: &P2, b: &P2) -> f64 {
+    na::distance_squared(point, &closest_on_segment(point, a, b))
+}
+
+// The axis-aligned axes also separate zero-length segments, whose normals are zero
+fn segments_cross(a: &[P2; 2], b: &[P2; 2]) -> bool {
+    let [normal_a, normal_b] = [a[1] - a[0], b[1] - b[0]].map(|d| vector![-d.y, d.x]);
+    !separated_on_any_axis(a, b, &[normal_a, normal_b, Vector2::x(), Vector2::y()])
+}
+
+fn segment_crosses_rect(segment: &[P2; 2], corners: &[P2; 4]) -> bool {
+    let d = segment[1] - segment[0];
+    !separated_on_any_axis(
+        segment,
+        corners,
+        &[vector![-d.y, d.x], Vector2::x(), Vector2::y()],
+    )
+}
+
+impl Shape for Polyline {
+    fn start(&self) -> P2 {
+        self.start
+    }
+
+    fn end(&self) -> P2 {
+        self.end
+    }
+
+    fn center(&self) -> P2 {
+        na::center(&self.start, &self.end)
+    }
+
+    fn contains(&self, point: &P2) -> bool {
+        let radius_squared = self.radius * self.radius;
+        self.segments()
+            .any(|(a, b)| distance_squared_to_segment(point, &a, &b) <= radius_squared)
+    }
+
+    fn intersects(&self, other: &Self) -> bool {
+        let reach = self.radius + other.radius;
+        let reach_squared = reach * reach;
+        self.segments().any(|(a, b)| {
+            other.segments().any(|(c, d)| {
+                segments_cross(&[a, b], &[c, d])
+                    || distance_squared_to_segment(&a, &c, &d) <= reach_squared
+                    || distance_squared_to_segment(&b, &c, &d) <= reach_squared
+                    || distance_squared_to_segment(&c, &a, &b) <= reach_squared
+                    || distance_squared_to_segment(&d, &a, &b) <= reach_squared
+            })
+        })
+    }
+
+    /// Check if the corridor around a single segment contains the rect, which is conservative
+    /// where the rect spans a joint
+    fn contains_rect(&self, rect: &Rect) -> bool {
+        let radius_squared = self.radius * self.radius;
+        let corners = rect.corners();
+        self.segments().any(|(a, b)| {
+            corners
+                .iter()
+                .all(|corner| distance_squared_to_segment(corner, &a, &b) <= radius_squared)
+        })
+    }
+
+    fn intersects_rect(&self, rect: &Rect) -> bool {
+        let radius_squared = self.radius * self.radius;
+        let corners = rect.corners();
+        self.segments().any(|(a, b)| {
+            segment_crosses_rect(&[a, b], &corners)
+                || distance_squared_to_rect(rect, &a) <= radius_squared
+                || distance_squared_to_rect(rect, &b) <= radius_squared
+                || corners
+                    .iter()
+                    .any(|corner| distance_squared_to_segment(corner, &a, &b) <= radius_squared)
+        })
+    }
+
+    /// Get the area of the corridor, which is exact for a straight path and approximate where
+    /// the path bends
+    fn area(&self) -> f64 {
+        if self.points.is_empty() {
+            return 0.0;
+        }
+        PI * self.radius * self.radius + 2.0 * self.radius * self.length()
+    }
+
+    /// Get the perimeter of the corridor, which is exact for a straight path and approximate
+    /// where the path bends
+    fn perimeter(&self) -> f64 {
+        if self.points.is_empty() {
+            return 0.0;
+        }
+        2.0 * PI * self.radius + 2.0 * self.length()
+    }
+
+    /// Get the signed distance to the edge of the corridor, which is exact outside of it and
+    /// along straight parts of the path, but may underestimate the depth inside a bend
+    fn distance_to(&self, point: &P2) -> f64 {
+        let distance = self
+            .segments()
+            .map(|(a, b)| na::distance(point, &closest_on_segment(point, &a, &b)))
+            .fold(f64::INFINITY, f64::min);
+        distance - self.radius
+    }
+}
+
+/// Represents a ray from an origin extending infinitely in a direction. A ray is unbounded, so it
+/// is not a [`Shape`], but it can be tested against rects for raycasting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Ray {
+    origin: P2,
+    direction: Vector2<f64>,
+}
+
+impl Ray {
+    /// Create a new ray with an origin and a direction, which is normalized
+    ///
+    /// **Returns** `None` if the direction is zero or any value is non-finite
+    pub fn try_new(origin: P2, direction: Vector2<f64>) -> Option<Self> {
+        if !is_finite(&origin) || !direction.iter().all(|d| d.is_finite()) {
+            return None;
+        }
+        let direction = direction.try_normalize(0.0)?;
+        Some(Self { origin, direction })
+    }
+
+    /// Get the origin of the ray
+    pub fn origin(&self) -> P2 {
+        self.origin
+    }
+
+    /// Get the unit direction of the ray
+    pub fn direction(&self) -> Vector2<f64> {
+        self.direction
+    }
+
+    /// Get the point at a distance along the ray
+    pub fn point_at(&self, distance: f64) -> P2 {
+        self.origin + self.direction * distance
+    }
+
+    /// Project a point onto the ray
+    ///
+    /// **Returns** the distance along the ray to the projection, which is negative for points
+    /// behind the origin, and the perpendicular distance from the ray's line to the point
+    pub fn project(&self, point: &P2) -> (f64, f64) {
+        let v = point - self.origin;
+        (v.dot(&self.direction), self.direction.perp(&v).abs())
+    }
+
+    /// Get the distance along the ray at which it enters a rect, using the slab method
+    ///
+    /// **Returns** `None` if the ray misses the rect, or `Some(0.0)` if the origin is inside it
+    pub fn entry_distance(&self, rect: &Rect) -> Option<f64> {
+        let (start, end) = (rect.start(), rect.end());
+        let mut t_enter = 0.0_f64;
+        let mut t_exit = f64::INFINITY;
+        for axis in 0..2 {
+            let (o, d) = (self.origin[axis], self.direction[axis]);
+            if d == 0.0 {
+                if o < start[axis] || o > end[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (start[axis] - o) / d;
+            let t2 = (end[axis] - o) / d;
+            t_enter = t_enter.max(t1.min(t2));
+            t_exit = t_exit.min(t1.max(t2));
+        }
+        (t_enter <= t_exit).then_some(t_enter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::tests::{make_circle, make_rect};
+    use alloc::vec;
+    use nalgebra::point;
+
+    use super::*;
+
+    #[test]
+    fn rect_from_center_and_size() {
+        let expected = make_rect(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(
+            Rect::from_center(point![5.0, 5.0], 5.0, 5.0),
+            expected,
+            "Rect from its center should match its corners"
+        );
+        assert_eq!(
+            Rect::from_size(point![0.0, 0.0], 10.0, 10.0),
+            expected,
+            "Rect from its size should match its corners"
+        );
+
+        let rect = Rect::from_center(point![2.0, -1.0], 3.0, 0.5);
+        assert_eq!(rect.start(), point![-1.0, -1.5]);
+        assert_eq!(rect.end(), point![5.0, -0.5]);
+        assert_eq!(rect, Rect::from_size(point![-1.0, -1.5], 6.0, 1.0));
+    }
+
+    #[test]
+    fn bounding_circles() {
+        let rect = make_rect(0.0, 0.0, 30.0, 40.0);
+        let circle = rect.bounding_circle();
+        assert_eq!(circle.center(), point![15.0, 20.0]);
+        assert!(
+            rect.corners().iter().all(|corner| circle.contains(corner)),
+            "Rect's bounding circle should contain all four corners"
+        );
+        assert!(
+            (circle.area() - PI * 625.0).abs() < 1e-9,
+            "Rect's bounding circle should pass through its corners"
+        );
+
+        let circle = make_circle(5.0, 5.0, 3.0);
+        assert_eq!(
+            circle.bounding_circle(),
+            circle,
+            "Circle's bounding circle should be itself"
+        );
+    }
+
+    #[test]
+    fn rect_dimensions() {
+        let rect = make_rect(1.0, 2.0, 4.0, 8.0);
+        assert_eq!(rect.width(), 3.0);
+        assert_eq!(rect.height(), 6.0);
+        assert!(!rect.is_degenerate());
+        assert!(
+            make_rect(1.0, 1.0, 1.0, 5.0).is_degenerate(),
+            "Zero width should be degenerate"
+        );
+        assert!(
+            make_rect(1.0, 5.0, 4.0, 1.0).is_degenerate(),
+            "Negative height should be degenerate"
+        );
+        assert!(
+            make_rect(0.0, 0.0, f64::NAN, 1.0).is_degenerate(),
+            "NaN width should be degenerate"
+        );
+    }
+
+    #[test]
+    fn rect_properties() {
+        let rect = make_rect(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(
+            rect.start(),
+            point![0.0, 0.0],
+            "Start should be at (0.0, 0.0)"
+        );
+        assert_eq!(
+            rect.end(),
+            point![10.0, 10.0],
+            "End should be at (10.0, 10.0)"
+        );
+        assert_eq!(
+            rect.center(),
+            point![5.0, 5.0],
+            "Center should be at (5.0, 5.0)"
+        );
+    }
+
+    #[test]
+    fn rect_contains_point() {
+        let rect = make_rect(0.0, 0.0, 10.0, 10.0);
+        assert!(
+            rect.contains(&point![5.0, 5.0]),
+            "Rect should contain point (5.0, 5.0)"
+        );
+        assert!(
+            !rect.contains(&point![-1.0, 5.0]),
+            "Rect should not contain point (-1.0, 5.0)"
+        );
+        assert!(
+            rect.contains(&point![0.0, 0.0]),
+            "Rect should contain its start point (0.0, 0.0)"
+        );
+        assert!(
+            rect.contains(&point![10.0, 10.0]),
+            "Rect should contain its end point (10.0, 10.0)"
+        );
+    }
+
+    #[test]
+    fn rect_from_tuple_and_array() {
+        let expected = make_rect(1.0, 2.0, 10.0, 20.0);
+        assert_eq!(Rect::from((point![1.0, 2.0], point![10.0, 20.0])), expected);
+        assert_eq!(Rect::from([1.0, 2.0, 10.0, 20.0]), expected);
+    }
+
+    #[test]
+    fn rect_contains_exclusive() {
+        let rect = make_rect(0.0, 0.0, 10.0, 10.0);
+        assert!(rect.contains(&point![10.0, 10.0]));
+        assert!(
+            !rect.contains_exclusive(&point![10.0, 10.0]),
+            "Exclusive containment should exclude the end point"
+        );
+        assert!(rect.contains_exclusive(&point![0.0, 0.0]));
+        assert!(rect.contains_exclusive(&point![5.0, 9.5]));
+        assert!(!rect.contains_exclusive(&point![10.0, 5.0]));
+        assert!(!rect.contains_exclusive(&point![5.0, 10.0]));
+
+        let right = make_rect(10.0, 0.0, 20.0, 10.0);
+        let shared = point![10.0, 5.0];
+        assert_eq!(
+            [
+                rect.contains_exclusive(&shared),
+                right.contains_exclusive(&shared)
+            ],
+            [false, true],
+            "A point on a shared edge should be in exactly one rect"
+        );
+    }
+
+    #[test]
+    fn rect_intersects_with_another_rect() {
+        let rect1 = make_rect(0.0, 0.0, 10.0, 10.0);
+        let rect2 = make_rect(5.0, 5.0, 15.0, 15.0);
+        assert!(
+            rect1.intersects(&rect2),
+            "Rect1 should intersect with Rect2"
+        );
+
+        let rect3 = make_rect(10.0, 10.0, 20.0, 20.0);
+        assert!(
+            rect1.intersects(&rect3),
+            "Rect1 should touch Rect3 at the edge, counting as intersect"
+        );
+
+        let rect4 = make_rect(11.0, 11.0, 21.0, 21.0);
+        assert!(
+            !rect1.intersects(&rect4),
+            "Rect1 should not intersect with Rect4"
+        );
+
+        let rect5 = make_rect(-5.0, -5.0, -1.0, -1.0);
+        assert!(
+            !rect1.intersects(&rect5),
+            "Rect1 should not intersect with Rect5"
+        );
+
+        let rect6 = make_rect(3.0, 3.0, 7.0, 7.0);
+        assert!(
+            rect1.intersects(&rect6),
+            "Rect6 is entirely inside Rect1, should intersect"
+        );
+
+        let rect7 = make_rect(-10.0, 0.0, -1.0, 10.0);
+        assert!(
+            !rect1.intersects(&rect7),
+            "Rect1 should not intersect with Rect7 on the left"
+        );
+
+        let rect8 = make_rect(0.0, -10.0, 10.0, -1.0);
+        assert!(
+            !rect1.intersects(&rect8),
+            "Rect1 should not intersect with Rect8 below it"
+        );
+
+        let rect9 = make_rect(0.0, 10.0, 10.0, 20.0);
+        assert!(
+            rect1.intersects(&rect9),
+            "Rect1 should touch Rect9 at the top, counting as intersect"
+        );
+
+        let rect10 = make_rect(10.0, 0.0, 20.0, 10.0);
+        assert!(
+            rect1.intersects(&rect10),
+            "Rect1 should touch Rect10 on the right, counting as intersect"
+        );
+
+        let rect11 = make_rect(5.0, -5.0, 15.0, 5.0);
+        assert!(
+            rect1.intersects(&rect11),
+            "Rect11 should intersect with the bottom part of Rect1"
+        );
+    }
+
+    #[test]
+    fn rect_contains_another_rect() {
+        let outer_rect = make_rect(0.0, 0.0, 10.0, 10.0);
+        let inner_rect = make_rect(1.0, 1.0, 9.0, 9.0);
+        assert!(
+            outer_rect.contains_rect(&inner_rect),
+            "Outer rect should contain inner rect completely"
+        );
+
+        let outer_rect = make_rect(0.0, 0.0, 10.0, 10.0);
+        let overlapping_rect = make_rect(5.0, 5.0, 15.0, 15.0);
+        assert!(
+            !outer_rect.contains_rect(&overlapping_rect),
+            "Outer rect should not contain overlapping rect"
+        );
+    }
+
+    #[test]
+    fn translate_and_scale_rect() {
+        let rect = make_rect(10.0, 20.0, 30.0, 30.0);
+        let scaled = rect.scale(3.0);
+        assert_eq!(
+            scaled.center(),
+            rect.center(),
+            "Scaling should keep the center"
+        );
+        assert_eq!(
+            scaled.end() - scaled.start(),
+            (rect.end() - rect.start()) * 3.0,
+            "Scaling should multiply the dimensions"
+        );
+        assert_eq!(scaled, make_rect(-10.0, 10.0, 50.0, 40.0));
+
+        let moved = rect.translate(vector![5.0, -20.0]);
+        assert_eq!(moved, make_rect(15.0, 0.0, 35.0, 10.0));
+        assert_eq!(moved.center(), point![25.0, 5.0], "Center should move");
+    }
+
+    #[test]
+    fn quartering_rect() {
+        let rect = make_rect(0.0, 0.0, 10.0, 10.0);
+        let quarters = rect.quarter();
+        // Ensure each quarter is correctly sized and positioned
+        assert_eq!(
+            quarters[0],
+            make_rect(0.0, 0.0, 5.0, 5.0),
+            "Top-left quarter should match expected dimensions"
+        );
+        assert_eq!(
+            quarters[1],
+            make_rect(5.0, 0.0, 10.0, 5.0),
+            "Top-right quarter should match expected dimensions"
+        );
+        assert_eq!(
+            quarters[2],
+            make_rect(0.0, 5.0, 5.0, 10.0),
+            "Bottom-left quarter should match expected dimensions"
+        );
+        assert_eq!(
+            quarters[3],
+            make_rect(5.0, 5.0, 10.0, 10.0),
+            "Bottom-right quarter should match expected dimensions"
+        );
+    }
+
+    #[test]
+    fn circle_properties_and_bounds() {
+        let circle = make_circle(5.0, 5.0, 5.0);
+        assert_eq!(
+            circle.center(),
+            point![5.0, 5.0],
+            "Center should be at (5.0, 5.0)"
+        );
+        assert_eq!(circle.radius, 5.0, "Radius should be 5.0");
+        assert_eq!(
+            circle.start(),
+            point![0.0, 0.0],
+            "Start should be at (0.0, 0.0)"
+        );
+        assert_eq!(
+            circle.end(),
+            point![10.0, 10.0],
+            "End should be at (10.0, 10.0)"
+        );
+    }
+
+    #[test]
+    fn circle_contains_point() {
+        let circle = make_circle(5.0, 5.0, 5.0);
+        assert!(
+            circle.contains(&point![5.0, 5.0]),
+            "Circle should contain its center point"
+        );
+        assert!(
+            circle.contains(&point![0.0, 5.0]),
+            "Circle should contain point on its perimeter"
+        );
+        assert!(
+            !circle.contains(&point![0.0, 0.0]),
+            "Circle should not contain points outside its boundary"
+        );
+
+        let negative = make_circle(5.0, 5.0, -5.0);
+        assert!(
+            !negative.contains(&point![5.0, 5.0]),
+            "Circle with a negative radius should not contain any points"
+        );
+        assert!(
+            !negative.intersects_rect(&make_rect(0.0, 0.0, 10.0, 10.0)),
+            "Circle with a negative radius should not intersect any rect"
+        );
+    }
+
+    #[test]
+    fn circle_boundary_inclusivity() {
+        let mut circle = make_circle(0.0, 0.0, 5.0);
+        let on_perimeter = point![3.0, 4.0];
+        assert!(
+            circle.is_inclusive(),
+            "Circles should be inclusive by default"
+        );
+        assert!(
+            circle.contains(&on_perimeter),
+            "Inclusive circle should contain a point at exactly its radius"
+        );
+
+        let exclusive = Circle::new_exclusive(point![0.0, 0.0], 5.0);
+        assert!(
+            !exclusive.contains(&on_perimeter),
+            "Exclusive circle should not contain a point at exactly its radius"
+        );
+        assert!(
+            exclusive.contains(&point![2.0, 2.0]),
+            "Exclusive circle should still contain interior points"
+        );
+        assert!(
+            !exclusive
+                .translate(vector![1.0, 0.0])
+                .contains(&point![4.0, 4.0]),
+            "Translating should preserve exclusivity"
+        );
+
+        circle.set_inclusive(false);
+        assert_eq!(
+            circle, exclusive,
+            "Setter should match the exclusive constructor"
+        );
+    }
+
+    #[test]
+    fn circle_contains_matches_distance() {
+        let mut circle = make_circle(5.0, 5.0, 5.0);
+        for radius in [0.0, 3.0, 5.0, 7.5] {
+            circle.set_radius(radius);
+            for x in -5..=15 {
+                for y in -5..=15 {
+                    let point = point![x as f64, y as f64];
+                    assert_eq!(
+                        circle.contains(&point),
+                        na::distance(&circle.center(), &point) <= radius,
+                        "Membership of {point} should match the distance check"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn circle_intersects_another_circle() {
         let circle1 = make_circle(5.0, 5.0, 5.0);
         let circle2 = make_circle(10.0, 5.0, 5.0);
         let circle3 = make_circle(20.0, 5.0, 5.0);
@@ -410,6 +1556,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn translate_and_scale_circle() {
+        let circle = make_circle(10.0, 10.0, 4.0);
+        let scaled = circle.scale(2.5);
+        assert_eq!(scaled, make_circle(10.0, 10.0, 10.0));
+        assert!(
+            scaled.contains(&point![19.0, 10.0]),
+            "Cached bounds should be recomputed"
+        );
+        assert_eq!(scaled.rect(), make_rect(0.0, 0.0, 20.0, 20.0));
+
+        let moved = circle.translate(vector![-10.0, 5.0]);
+        assert_eq!(moved, make_circle(0.0, 15.0, 4.0));
+        assert_eq!(moved.rect(), make_rect(-4.0, 11.0, 4.0, 19.0));
+    }
+
+    #[test]
+    fn circle_try_new() {
+        assert!(
+            Circle::try_new(point![5.0, 5.0], 5.0).is_some(),
+            "Circle with a valid radius should be created"
+        );
+        assert!(
+            Circle::try_new(point![5.0, 5.0], -1.0).is_none(),
+            "Circle with a negative radius should be rejected"
+        );
+        assert!(
+            Circle::try_new(point![5.0, 5.0], f64::NAN).is_none(),
+            "Circle with a NaN radius should be rejected"
+        );
+        assert!(
+            Circle::try_new(point![f64::INFINITY, 5.0], 5.0).is_none(),
+            "Circle with a non-finite center should be rejected"
+        );
+    }
+
     #[test]
     fn circle_contains_rect() {
         let circle = make_circle(5.0, 5.0, 5.0);
@@ -424,6 +1606,527 @@ mod tests {
         );
     }
 
+    #[test]
+    fn circle_intersects_rect() {
+        let circle = make_circle(0.0, 0.0, 10.0);
+        assert!(circle.intersects_rect(&make_rect(5.0, 5.0, 20.0, 20.0)));
+        assert!(
+            circle.intersects_rect(&make_rect(10.0, -5.0, 20.0, 5.0)),
+            "Circle should intersect rect touching its edge"
+        );
+        assert!(
+            !circle.intersects_rect(&make_rect(8.0, 8.0, 20.0, 20.0)),
+            "Circle should not intersect rect only in its bounding box corner"
+        );
+        assert!(
+            circle.intersects_rect(&make_rect(-50.0, -50.0, 50.0, 50.0)),
+            "Circle should intersect rect containing it"
+        );
+    }
+
+    #[test]
+    fn half_plane_contains_and_intersects() {
+        let half_plane = HalfPlane::new(point![5.0, 0.0], vector![1.0, 0.0]);
+        assert!(
+            half_plane.contains(&point![10.0, -100.0]),
+            "Half-plane should contain point on the positive side"
+        );
+        assert!(
+            half_plane.contains(&point![5.0, 3.0]),
+            "Half-plane should contain point on its edge"
+        );
+        assert!(
+            !half_plane.contains(&point![4.0, 3.0]),
+            "Half-plane should not contain point on the negative side"
+        );
+
+        assert!(
+            half_plane.contains_rect(&make_rect(6.0, 0.0, 10.0, 10.0)),
+            "Half-plane should contain rect on the positive side"
+        );
+        assert!(
+            !half_plane.contains_rect(&make_rect(0.0, 0.0, 10.0, 10.0)),
+            "Half-plane should not contain rect crossing its edge"
+        );
+        assert!(
+            half_plane.intersects_rect(&make_rect(0.0, 0.0, 10.0, 10.0)),
+            "Half-plane should intersect rect crossing its edge"
+        );
+        assert!(
+            !half_plane.intersects_rect(&make_rect(0.0, 0.0, 4.0, 10.0)),
+            "Half-plane should not intersect rect on the negative side"
+        );
+
+        let facing = HalfPlane::new(point![8.0, 0.0], vector![-1.0, 0.0]);
+        let away = HalfPlane::new(point![2.0, 0.0], vector![-1.0, 0.0]);
+        let tilted = HalfPlane::new(point![0.0, 0.0], vector![-1.0, 1.0]);
+        assert!(
+            half_plane.intersects(&facing),
+            "Opposite half-planes overlapping between their edges should intersect"
+        );
+        assert!(
+            !half_plane.intersects(&away),
+            "Opposite half-planes facing away from each other should not intersect"
+        );
+        assert!(
+            half_plane.intersects(&tilted),
+            "Non-parallel half-planes should always intersect"
+        );
+    }
+
+    #[test]
+    fn oriented_rect_contains_rotated_corners() {
+        let rect = OrientedRect::new(
+            point![5.0, 5.0],
+            vector![2.0, 2.0],
+            core::f64::consts::FRAC_PI_4,
+        );
+        assert!(
+            rect.contains(&point![5.0, 5.0]),
+            "Oriented rect should contain its center"
+        );
+        assert!(
+            rect.contains(&point![7.8, 5.0]),
+            "Oriented rect should contain point just inside its rotated corner"
+        );
+        assert!(
+            !rect.contains(&point![7.9, 5.0]),
+            "Oriented rect should not contain point just beyond its rotated corner"
+        );
+        assert!(
+            !rect.contains(&point![6.9, 6.9]),
+            "Oriented rect should not contain point near the unrotated corner"
+        );
+
+        let diagonal = 2.0 * core::f64::consts::SQRT_2;
+        let bounds = rect.rect();
+        assert!(
+            (bounds.start() - point![5.0 - diagonal, 5.0 - diagonal]).norm() < 1e-9,
+            "Bounding rect should start at the rotated corners"
+        );
+        assert!(
+            (bounds.end() - point![5.0 + diagonal, 5.0 + diagonal]).norm() < 1e-9,
+            "Bounding rect should end at the rotated corners"
+        );
+    }
+
+    #[test]
+    fn oriented_rect_intersects() {
+        let rect = OrientedRect::new(
+            point![5.0, 5.0],
+            vector![2.0, 2.0],
+            core::f64::consts::FRAC_PI_4,
+        );
+        assert!(
+            rect.intersects_rect(&make_rect(7.0, 4.0, 9.0, 6.0)),
+            "Oriented rect should intersect rect overlapping its rotated corner"
+        );
+        assert!(
+            !rect.intersects_rect(&make_rect(7.0, 7.0, 9.0, 9.0)),
+            "Oriented rect should not intersect rect within only its bounding box"
+        );
+        assert!(
+            rect.contains_rect(&make_rect(4.0, 4.0, 6.0, 6.0)),
+            "Oriented rect should contain small rect at its center"
+        );
+
+        let other = OrientedRect::new(
+            point![9.0, 5.0],
+            vector![1.5, 1.5],
+            core::f64::consts::FRAC_PI_4,
+        );
+        let far = OrientedRect::new(point![9.0, 9.0], vector![1.5, 1.5], 0.0);
+        assert!(
+            rect.intersects(&other),
+            "Oriented rects touching at rotated corners should intersect"
+        );
+        assert!(
+            !rect.intersects(&far),
+            "Oriented rects separated along the diagonal should not intersect"
+        );
+    }
+
+    #[test]
+    fn sector_contains_point() {
+        use core::f64::consts::{FRAC_PI_4, PI};
+
+        let sector = Sector::new(point![0.0, 0.0], 10.0, 0.0, FRAC_PI_4);
+        assert!(
+            sector.contains(&point![5.0, 0.0]),
+            "Sector should contain point in its facing direction"
+        );
+        assert!(
+            sector.contains(&point![5.0, 4.9]),
+            "Sector should contain point just inside its angular span"
+        );
+        assert!(
+            !sector.contains(&point![5.0, 5.1]),
+            "Sector should not contain point just outside its angular span"
+        );
+        assert!(
+            !sector.contains(&point![-5.0, 0.0]),
+            "Sector should not contain point behind it"
+        );
+        assert!(
+            !sector.contains(&point![11.0, 0.0]),
+            "Sector should not contain point beyond its radius"
+        );
+
+        let wrapped = Sector::new(point![0.0, 0.0], 10.0, PI, FRAC_PI_4);
+        assert!(
+            wrapped.contains(&point![-5.0, 1.0]),
+            "Sector should contain point above the -x axis"
+        );
+        assert!(
+            wrapped.contains(&point![-5.0, -1.0]),
+            "Sector should contain point below the -x axis across the wraparound"
+        );
+        assert!(
+            !wrapped.contains(&point![1.0, -5.0]),
+            "Sector should not contain point outside its wrapped span"
+        );
+    }
+
+    #[test]
+    fn sector_contains_rect() {
+        use core::f64::consts::{FRAC_PI_4, PI};
+
+        let sector = Sector::new(point![0.0, 0.0], 10.0, 0.0, FRAC_PI_4);
+        assert!(
+            sector.contains_rect(&make_rect(4.0, -1.0, 6.0, 1.0)),
+            "Sector should contain rect within its span"
+        );
+        assert!(
+            !sector.contains_rect(&make_rect(4.0, -1.0, 6.0, 6.0)),
+            "Sector should not contain rect crossing its edge"
+        );
+
+        let reflex = Sector::new(point![0.0, 0.0], 10.0, 0.0, 3.0 * PI / 4.0);
+        let spanning_gap = make_rect(-5.0, -6.0, -1.0, 6.0);
+        assert!(
+            spanning_gap
+                .corners()
+                .iter()
+                .all(|corner| reflex.contains(corner)),
+            "Non-convex sector should contain every corner of the rect"
+        );
+        assert!(
+            !reflex.contains_rect(&spanning_gap),
+            "Non-convex sector should not contain rect spanning its gap"
+        );
+    }
+
+    #[test]
+    fn triangle_contains_point() {
+        let triangle = Triangle::new(point![0.0, 0.0], point![10.0, 0.0], point![0.0, 10.0]);
+        assert!(
+            triangle.contains(&point![2.0, 3.0]),
+            "Triangle should contain point inside"
+        );
+        assert!(
+            triangle.contains(&point![5.0, 5.0]),
+            "Triangle should contain point on its hypotenuse"
+        );
+        assert!(
+            !triangle.contains(&point![5.1, 5.0]),
+            "Triangle should not contain point just outside its hypotenuse"
+        );
+        assert_eq!(
+            triangle.center(),
+            point![10.0 / 3.0, 10.0 / 3.0],
+            "Center should be the centroid"
+        );
+        assert_eq!(
+            triangle.rect(),
+            make_rect(0.0, 0.0, 10.0, 10.0),
+            "Rect should bound the vertices"
+        );
+
+        let reversed = Triangle::new(point![0.0, 0.0], point![0.0, 10.0], point![10.0, 0.0]);
+        assert!(
+            reversed.contains(&point![2.0, 3.0]),
+            "Winding order should not matter"
+        );
+
+        let degenerate = Triangle::new(point![0.0, 0.0], point![5.0, 5.0], point![10.0, 10.0]);
+        assert!(
+            !degenerate.contains(&point![5.0, 5.0]),
+            "Degenerate triangle should contain nothing"
+        );
+        assert!(
+            !degenerate.intersects_rect(&make_rect(0.0, 0.0, 10.0, 10.0)),
+            "Degenerate triangle should intersect nothing"
+        );
+    }
+
+    #[test]
+    fn triangle_intersects_and_contains_rect() {
+        let triangle = Triangle::new(point![0.0, 0.0], point![10.0, 0.0], point![0.0, 10.0]);
+        assert!(
+            triangle.contains_rect(&make_rect(1.0, 1.0, 3.0, 3.0)),
+            "Triangle should contain rect inside it"
+        );
+        assert!(
+            !triangle.contains_rect(&make_rect(4.0, 4.0, 6.0, 6.0)),
+            "Triangle should not contain rect crossing its hypotenuse"
+        );
+        assert!(
+            triangle.intersects_rect(&make_rect(4.0, 4.0, 6.0, 6.0)),
+            "Triangle should intersect rect crossing its hypotenuse"
+        );
+        assert!(
+            !triangle.intersects_rect(&make_rect(6.0, 6.0, 9.0, 9.0)),
+            "Triangle should not intersect rect within its bounding box but beyond its hypotenuse"
+        );
+    }
+
+    #[test]
+    fn ray_entry_distance() {
+        let ray = Ray::try_new(point![0.0, 5.0], vector![2.0, 0.0]).unwrap();
+        assert_eq!(
+            ray.direction(),
+            vector![1.0, 0.0],
+            "Direction should be normalized"
+        );
+        assert_eq!(
+            ray.entry_distance(&make_rect(10.0, 0.0, 20.0, 10.0)),
+            Some(10.0),
+            "Ray should enter rect at its near edge"
+        );
+        assert_eq!(
+            ray.entry_distance(&make_rect(-5.0, 0.0, 5.0, 10.0)),
+            Some(0.0),
+            "Ray starting inside rect should enter immediately"
+        );
+        assert_eq!(
+            ray.entry_distance(&make_rect(10.0, 6.0, 20.0, 10.0)),
+            None,
+            "Ray parallel to and outside of a slab should miss"
+        );
+        assert_eq!(
+            ray.entry_distance(&make_rect(-20.0, 0.0, -10.0, 10.0)),
+            None,
+            "Rect behind the origin should be missed"
+        );
+
+        let diagonal = Ray::try_new(point![0.0, 0.0], vector![1.0, 1.0]).unwrap();
+        assert!(
+            diagonal
+                .entry_distance(&make_rect(10.0, 0.0, 20.0, 5.0))
+                .is_none(),
+            "Diagonal ray should pass beside rect"
+        );
+        let (along, across) = diagonal.project(&point![2.0, 0.0]);
+        assert!(
+            (along - core::f64::consts::SQRT_2).abs() < 1e-12
+                && (across - core::f64::consts::SQRT_2).abs() < 1e-12,
+            "Projection should split the offset along and across the ray"
+        );
+        assert!(Ray::try_new(point![0.0, 0.0], vector![0.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn shape_area_and_perimeter() {
+        let rect = make_rect(0.0, 0.0, 4.0, 3.0);
+        assert_eq!(rect.area(), 12.0, "Rect area should be width times height");
+        assert_eq!(rect.perimeter(), 14.0, "Rect perimeter should be 2(w + h)");
+
+        let circle = make_circle(0.0, 0.0, 2.0);
+        assert_eq!(circle.area(), 4.0 * PI, "Circle area should be pi r^2");
+        assert_eq!(
+            circle.perimeter(),
+            4.0 * PI,
+            "Circle perimeter should be 2 pi r"
+        );
+
+        let triangle = Triangle::new(point![0.0, 0.0], point![3.0, 0.0], point![0.0, 4.0]);
+        assert_eq!(triangle.area(), 6.0);
+        assert_eq!(triangle.perimeter(), 12.0);
+
+        let oriented = OrientedRect::new(point![0.0, 0.0], vector![2.0, 1.5], 0.7);
+        assert_eq!(oriented.area(), 12.0);
+        assert_eq!(oriented.perimeter(), 14.0);
+
+        let semicircle = Sector::new(point![0.0, 0.0], 2.0, 0.0, PI / 2.0);
+        assert_eq!(semicircle.area(), 2.0 * PI);
+        assert_eq!(semicircle.perimeter(), 2.0 * PI + 4.0);
+
+        assert_eq!(point![1.0, 2.0].area(), 0.0, "A point should have no area");
+    }
+
+    #[test]
+    fn polyline_contains_point() {
+        let route = Polyline::new(
+            vec![point![0.0, 0.0], point![10.0, 0.0], point![10.0, 10.0]],
+            1.0,
+        );
+        assert!(
+            route.contains(&point![10.5, 0.5]),
+            "Polyline should contain point near its interior joint"
+        );
+        assert!(
+            route.contains(&point![11.0, 5.0]),
+            "Polyline should contain point at its radius from a segment"
+        );
+        assert!(
+            !route.contains(&point![5.0, 5.0]),
+            "Polyline should not contain point inside the bend but away from both segments"
+        );
+        assert!(
+            !route.contains(&point![-1.5, 0.0]),
+            "Polyline should not contain point beyond its end"
+        );
+        assert_eq!(
+            route.rect(),
+            make_rect(-1.0, -1.0, 11.0, 11.0),
+            "Rect should be the inflated bounding box of the points"
+        );
+        assert!(route.intersects_rect(&make_rect(10.5, 4.0, 12.0, 6.0)));
+        assert!(!route.intersects_rect(&make_rect(3.0, 3.0, 7.0, 7.0)));
+        assert!(route.contains_rect(&make_rect(2.0, -0.5, 8.0, 0.5)));
+
+        let single = Polyline::new(vec![point![5.0, 5.0]], 2.0);
+        let circle = make_circle(5.0, 5.0, 2.0);
+        for point in [point![6.0, 6.0], point![7.0, 5.0], point![6.5, 6.5]] {
+            assert_eq!(
+                single.contains(&point),
+                circle.contains(&point),
+                "Single-point polyline should behave like a circle at {point:?}"
+            );
+        }
+
+        let empty = Polyline::new(vec![], 1.0);
+        assert!(
+            !empty.contains(&point![0.0, 0.0]),
+            "Empty polyline should contain nothing"
+        );
+    }
+
+    #[test]
+    fn signed_distance_to_edge() {
+        use core::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4, SQRT_2};
+
+        let rect = make_rect(0.0, 0.0, 10.0, 6.0);
+        assert_eq!(
+            rect.distance_to(&point![5.0, 3.0]),
+            -3.0,
+            "Center is 3 from nearest edge"
+        );
+        assert_eq!(
+            rect.distance_to(&point![9.0, 3.0]),
+            -1.0,
+            "Inside near the right edge"
+        );
+        assert_eq!(rect.distance_to(&point![10.0, 2.0]), 0.0, "On the edge");
+        assert_eq!(
+            rect.distance_to(&point![14.0, 3.0]),
+            4.0,
+            "Outside beside an edge"
+        );
+        assert_eq!(
+            rect.distance_to(&point![13.0, 10.0]),
+            5.0,
+            "Outside beyond a corner"
+        );
+
+        let circle = make_circle(0.0, 0.0, 5.0);
+        assert_eq!(
+            circle.distance_to(&point![0.0, 0.0]),
+            -5.0,
+            "Center is a radius inside"
+        );
+        assert_eq!(
+            circle.distance_to(&point![3.0, 0.0]),
+            -2.0,
+            "Inside the circle"
+        );
+        assert_eq!(
+            circle.distance_to(&point![6.0, 8.0]),
+            5.0,
+            "Outside the circle"
+        );
+
+        let half_plane = HalfPlane::new(point![0.0, 0.0], vector![0.0, 2.0]);
+        assert_eq!(half_plane.distance_to(&point![3.0, 4.0]), -4.0);
+        assert_eq!(half_plane.distance_to(&point![3.0, -1.0]), 1.0);
+
+        let assert_close = |actual: f64, expected: f64, msg: &str| {
+            assert!(
+                (actual - expected).abs() < 1e-9,
+                "{msg}: {actual} != {expected}"
+            );
+        };
+
+        let triangle = Triangle::new(point![0.0, 0.0], point![10.0, 0.0], point![0.0, 10.0]);
+        assert_close(
+            triangle.distance_to(&point![2.0, 2.0]),
+            -2.0,
+            "Inside the triangle",
+        );
+        assert_close(
+            triangle.distance_to(&point![-3.0, -4.0]),
+            5.0,
+            "Beyond a vertex",
+        );
+        assert_close(
+            triangle.distance_to(&point![6.0, 6.0]),
+            SQRT_2,
+            "Beyond the hypotenuse",
+        );
+
+        let oriented = OrientedRect::new(point![0.0, 0.0], vector![2.0, 1.0], FRAC_PI_2);
+        assert_close(
+            oriented.distance_to(&point![0.0, 1.0]),
+            -1.0,
+            "Inside the rotated rect",
+        );
+        assert_close(
+            oriented.distance_to(&point![0.0, 4.0]),
+            2.0,
+            "Beyond a rotated edge",
+        );
+        assert_close(
+            oriented.distance_to(&point![4.0, 6.0]),
+            5.0,
+            "Beyond a rotated corner",
+        );
+
+        let sector = Sector::new(point![0.0, 0.0], 10.0, 0.0, FRAC_PI_4);
+        assert_close(
+            sector.distance_to(&point![5.0, 0.0]),
+            -5.0 * FRAC_1_SQRT_2,
+            "Inside the sector",
+        );
+        assert_close(
+            sector.distance_to(&point![-3.0, 0.0]),
+            3.0,
+            "Behind the center",
+        );
+        assert_close(
+            sector.distance_to(&point![0.0, 5.0]),
+            5.0 * FRAC_1_SQRT_2,
+            "Beside an edge",
+        );
+        assert_close(
+            sector.distance_to(&point![20.0, 0.0]),
+            10.0,
+            "Beyond the arc",
+        );
+
+        let polyline = Polyline::new(vec![point![0.0, 0.0], point![10.0, 0.0]], 1.0);
+        assert_close(polyline.distance_to(&point![5.0, 0.0]), -1.0, "On the path");
+        assert_close(
+            polyline.distance_to(&point![5.0, 3.0]),
+            2.0,
+            "Beside the path",
+        );
+        assert_close(
+            polyline.distance_to(&point![13.0, 4.0]),
+            4.0,
+            "Beyond the end",
+        );
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn serialize_rect() {
@@ -0,0 +1,74 @@
+//! Policies for whether clones of a quadtree share their nodes
+
+use alloc::sync::Arc;
+
+use crate::quadtree::Node;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Trait for whether the clones of a quadtree share their nodes with the original
+///
+/// This trait is sealed. [`Unique`] implements it for every item type, and [`Shared`] for item
+/// types that implement `Clone`, since a shared node has to be copied before it is modified.
+pub trait Sharing<T>: private::Sealed {
+    /// Get mutable access to a child node, copying it first if it is shared
+    #[doc(hidden)]
+    fn make_mut(node: &mut Arc<Node<T>>) -> &mut Node<T>;
+
+    /// Clone a child node for a clone of the tree
+    #[doc(hidden)]
+    fn clone_child(node: &Arc<Node<T>>) -> Arc<Node<T>>
+    where
+        T: Clone;
+}
+
+/// Sharing policy where every clone of a quadtree owns a copy of each node
+///
+/// Nodes are never shared, so they are modified in place and mutations don't require
+/// `T: Clone`. This is the policy used by [`QuadTree`](crate::QuadTree) unless another one is
+/// chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Unique;
+
+impl private::Sealed for Unique {}
+
+impl<T> Sharing<T> for Unique {
+    #[inline(always)]
+    fn make_mut(node: &mut Arc<Node<T>>) -> &mut Node<T> {
+        Arc::get_mut(node).expect("node should not be shared")
+    }
+
+    fn clone_child(node: &Arc<Node<T>>) -> Arc<Node<T>>
+    where
+        T: Clone,
+    {
+        Arc::new(node.clone_with(&<Self as Sharing<T>>::clone_child))
+    }
+}
+
+/// Sharing policy where the clones of a quadtree share their nodes with the original
+///
+/// Cloning only copies the root, and every other node is copied on write once either tree
+/// modifies it, so clones never observe each other's changes. This makes it cheap to keep a
+/// consistent snapshot of a tree that is still being mutated. See
+/// [`SharedQuadTree`](crate::SharedQuadTree).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Shared;
+
+impl private::Sealed for Shared {}
+
+impl<T: Clone> Sharing<T> for Shared {
+    #[inline(always)]
+    fn make_mut(node: &mut Arc<Node<T>>) -> &mut Node<T> {
+        Arc::make_mut(node)
+    }
+
+    fn clone_child(node: &Arc<Node<T>>) -> Arc<Node<T>>
+    where
+        T: Clone,
+    {
+        Arc::clone(node)
+    }
+}
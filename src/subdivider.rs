@@ -0,0 +1,36 @@
+//! Policies for dividing a node's boundary into the boundaries of its children
+
+use crate::shapes::Rect;
+
+/// Trait for dividing the boundary of a node into the boundaries of its four children
+///
+/// The children must tile the boundary as a 2x2 grid in the same order as [`Rect::quarter`],
+/// meeting at a split point strictly inside the boundary, which is the end of the first child.
+/// Points are routed to children by which side of the split point they are on, so the split
+/// doesn't need to be at the center.
+///
+/// This is implemented for every `Fn(&Rect) -> [Rect; 4]`.
+pub trait Subdivider {
+    /// Get the boundaries of the children of a node with boundary `rect`
+    fn subdivide(&self, rect: &Rect) -> [Rect; 4];
+}
+
+/// Subdivider that splits a boundary into four equal quarters with [`Rect::quarter`]
+///
+/// This is the subdivider used by [`QuadTree::new`](crate::QuadTree::new) and every other
+/// constructor that doesn't take a subdivider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quarter;
+
+impl Subdivider for Quarter {
+    #[inline(always)]
+    fn subdivide(&self, rect: &Rect) -> [Rect; 4] {
+        rect.quarter()
+    }
+}
+
+impl<F: Fn(&Rect) -> [Rect; 4]> Subdivider for F {
+    fn subdivide(&self, rect: &Rect) -> [Rect; 4] {
+        self(rect)
+    }
+}
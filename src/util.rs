@@ -1,21 +1,42 @@
+use alloc::{vec, vec::Vec};
+use core::cmp::Ordering;
+
 use crate::{
+    key::KeyFn,
     shapes::{Rect, Shape},
-    Point,
+    P2,
 };
 
-pub(crate) fn determine_quadrant<T: Point>(rect: &Rect, item: &T) -> Option<usize> {
-    for (i, rect) in rect.quarter().iter().enumerate() {
-        if rect.contains(&item.point()) {
-            return Some(i);
-        }
+pub(crate) fn is_finite(point: &P2) -> bool {
+    point.x.is_finite() && point.y.is_finite()
+}
+
+/// Quarters are half-open on their shared edges, so a point on an internal edge belongs to the
+/// quarter on its greater side and every point in `rect` maps to exactly one quadrant
+pub(crate) fn determine_quadrant(rect: &Rect, point: &P2) -> Option<usize> {
+    split_quadrant(rect, &rect.center(), point)
+}
+
+/// Like [`determine_quadrant`], but for a rect divided at `split` instead of at its center
+pub(crate) fn split_quadrant(rect: &Rect, split: &P2, point: &P2) -> Option<usize> {
+    if !rect.contains(point) {
+        return None;
     }
-    None
+
+    let col = (point.x >= split.x) as usize;
+    let row = (point.y >= split.y) as usize;
+    Some(row * 2 + col)
 }
 
-pub(crate) fn group_by_quadrant<T: Point>(rect: &Rect, items: Vec<T>) -> [Vec<T>; 5] {
-    let mut groups: [Vec<T>; 5] = std::array::from_fn(|_| Vec::with_capacity(items.len()));
+pub(crate) fn group_by_quadrant<T, K: KeyFn<T>>(
+    rect: &Rect,
+    split: &P2,
+    items: Vec<T>,
+    key: &K,
+) -> [Vec<T>; 5] {
+    let mut groups: [Vec<T>; 5] = core::array::from_fn(|_| Vec::with_capacity(items.len()));
     for item in items {
-        match determine_quadrant(rect, &item) {
+        match split_quadrant(rect, split, &key.point(&item)) {
             Some(q) => groups[q].push(item),
             None => groups[4].push(item),
         }
@@ -24,13 +45,14 @@ pub(crate) fn group_by_quadrant<T: Point>(rect: &Rect, items: Vec<T>) -> [Vec<T>
 }
 
 #[allow(unused)]
-pub(crate) fn group_by_quadrant_slice<'a, T: Point>(
+pub(crate) fn group_by_quadrant_slice<'a, T, K: KeyFn<T>>(
     rect: &Rect,
     items: &'a [T],
+    key: &K,
 ) -> [Vec<&'a T>; 5] {
-    let mut groups: [Vec<&T>; 5] = std::array::from_fn(|_| Vec::with_capacity(items.len()));
+    let mut groups: [Vec<&T>; 5] = core::array::from_fn(|_| Vec::with_capacity(items.len()));
     for item in items {
-        match determine_quadrant(rect, item) {
+        match determine_quadrant(rect, &key.point(item)) {
             Some(q) => groups[q].push(item),
             None => groups[4].push(item),
         }
@@ -38,21 +60,111 @@ pub(crate) fn group_by_quadrant_slice<'a, T: Point>(
     groups
 }
 
-pub(crate) fn determine_overlap_quadrants(outer: &Rect, inner: &Rect) -> Vec<usize> {
+// Uses the shape's own intersection test rather than its bounding rect, so children that only
+// overlap the bounding rect of a non-rectangular shape are skipped
+pub(crate) fn determine_overlap_quadrants<S: Shape + ?Sized>(
+    quarters: &[Rect; 4],
+    shape: &S,
+) -> Vec<usize> {
     let mut quadrants = Vec::with_capacity(4);
-    for (i, rect) in outer.quarter().iter().enumerate() {
-        if rect.intersects(inner) {
+    for (i, rect) in quarters.iter().enumerate() {
+        if shape.intersects_rect(rect) {
             quadrants.push(i);
         }
     }
     quadrants
 }
 
+pub(crate) fn distance_squared_to_rect(rect: &Rect, point: &P2) -> f64 {
+    let closest = point.sup(&rect.start()).inf(&rect.end());
+    nalgebra::distance_squared(&closest, point)
+}
+
+// Orders points by x and then by y, falling back to a total order for values that don't compare
+pub(crate) fn cmp_xy(a: &P2, b: &P2) -> Ordering {
+    let cmp = |a: f64, b: f64| a.partial_cmp(&b).unwrap_or_else(|| a.total_cmp(&b));
+    cmp(a.x, b.x).then_with(|| cmp(a.y, b.y))
+}
+
+pub(crate) fn intersects_any<S: Shape>(rect: &Rect, shapes: &[S]) -> bool {
+    shapes.iter().any(|shape| shape.intersects_rect(rect))
+}
+
+pub(crate) fn contains_any<S: Shape>(shapes: &[S], point: &P2) -> bool {
+    shapes.iter().any(|shape| shape.contains(point))
+}
+
+pub(crate) fn grid_cell(region: &Rect, cols: usize, rows: usize, point: &P2) -> usize {
+    let offset = point - region.start();
+    let size = region.end() - region.start();
+    let col = ((offset.x / size.x * cols as f64) as usize).min(cols - 1);
+    let row = ((offset.y / size.y * rows as f64) as usize).min(rows - 1);
+    row * cols + col
+}
+
+pub(crate) fn expand_rect(rect: &Rect, margin: f64) -> Rect {
+    let v = nalgebra::vector![margin, margin];
+    Rect::new(rect.start() - v, rect.end() + v)
+}
+
+// Linearly interpolates between the closest ranks, reordering `values` in the process
+pub(crate) fn quantile(values: &mut [f64], q: f64) -> Option<f64> {
+    if values.is_empty() || !(0.0..=1.0).contains(&q) {
+        return None;
+    }
+
+    let position = q * (values.len() - 1) as f64;
+    let lower = position as usize;
+    let fraction = position - lower as f64;
+    let (_, &mut low, above) = values.select_nth_unstable_by(lower, f64::total_cmp);
+    if fraction == 0.0 {
+        return Some(low);
+    }
+    let high = above.iter().copied().min_by(f64::total_cmp)?;
+    Some(low + (high - low) * fraction)
+}
+
+// Disjoint sets of indices with path halving and union by size
+pub(crate) struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    pub(crate) fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    pub(crate) fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    pub(crate) fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            core::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
+    use alloc::vec;
     use nalgebra::point;
 
-    use crate::shapes::*;
+    use crate::{key::PointKey, shapes::*};
 
     use super::*;
 
@@ -87,6 +199,30 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn determine_quadrant_on_internal_edges() {
+        let rect = make_rect(0.0, 0.0, 10.0, 10.0);
+        let cases = [
+            (point![5.0, 5.0], 3),
+            (point![5.0, 2.5], 1),
+            (point![2.5, 5.0], 2),
+            (point![0.0, 0.0], 0),
+            (point![10.0, 10.0], 3),
+            (point![10.0, 0.0], 1),
+        ];
+        for (point, expected) in cases {
+            assert_eq!(
+                determine_quadrant(&rect, &point),
+                Some(expected),
+                "Point {point:?} should belong to the quarter on its greater side"
+            );
+            assert!(
+                rect.quarter()[expected].contains(&point),
+                "The assigned quarter should contain the point"
+            );
+        }
+    }
+
     #[test]
     fn test_group_by_quadrant() {
         let rect = make_rect(0.0, 0.0, 10.0, 10.0);
@@ -106,7 +242,7 @@ pub(crate) mod tests {
             vec![point![10.5, 10.5]],
         ];
 
-        let results = group_by_quadrant(&rect, points.to_vec());
+        let results = group_by_quadrant(&rect, &rect.center(), points.to_vec(), &PointKey);
 
         for (expected, result) in expected_groups.iter().zip(results.iter()) {
             assert_eq!(
@@ -123,7 +259,7 @@ pub(crate) mod tests {
         // Test with an inner rectangle that intersects multiple quadrants
         let inner_multiple_overlap = make_rect(25.0, 25.0, 75.0, 75.0);
         assert_eq!(
-            determine_overlap_quadrants(&outer, &inner_multiple_overlap),
+            determine_overlap_quadrants(&outer.quarter(), &inner_multiple_overlap),
             &[0, 1, 2, 3],
             "Inner rectangle overlaps all quadrants."
         );
@@ -131,7 +267,7 @@ pub(crate) mod tests {
         // Test with an inner rectangle that overlaps only one quadrant
         let inner_single_overlap = make_rect(10.0, 10.0, 30.0, 30.0);
         assert_eq!(
-            determine_overlap_quadrants(&outer, &inner_single_overlap),
+            determine_overlap_quadrants(&outer.quarter(), &inner_single_overlap),
             &[0],
             "Inner rectangle overlaps only the first quadrant."
         );
@@ -139,14 +275,14 @@ pub(crate) mod tests {
         // Test with an inner rectangle that does not overlap any quadrant
         let inner_no_overlap = make_rect(101.0, 101.0, 150.0, 150.0);
         assert!(
-            determine_overlap_quadrants(&outer, &inner_no_overlap).is_empty(),
+            determine_overlap_quadrants(&outer.quarter(), &inner_no_overlap).is_empty(),
             "Inner rectangle does not overlap any quadrant."
         );
 
         // Test with an inner rectangle that overlaps on the boundary between two quadrants
         let inner_boundary_overlap = make_rect(50.0, 50.0, 70.0, 70.0);
         assert_eq!(
-            determine_overlap_quadrants(&outer, &inner_boundary_overlap),
+            determine_overlap_quadrants(&outer.quarter(), &inner_boundary_overlap),
             &[0, 1, 2, 3],
             "Inner rectangle overlaps the boundary between all quadrants."
         );
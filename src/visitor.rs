@@ -0,0 +1,15 @@
+//! Callbacks for walking the node hierarchy of a quadtree
+
+use crate::shapes::Rect;
+
+/// Trait for visiting the nodes of a quadtree, see [`QuadTree::walk`](crate::QuadTree::walk)
+///
+/// Nodes are visited depth-first, with each internal node visited before its four children,
+/// which are visited in the order of [`Rect::quarter`]. The root is at depth 0.
+pub trait NodeVisitor<T> {
+    /// Visit an internal node, which has been subdivided into four children
+    fn visit_internal(&mut self, boundary: Rect, depth: usize);
+
+    /// Visit a leaf node along with the items it stores, which may be none
+    fn visit_leaf(&mut self, boundary: Rect, depth: usize, items: &[T]);
+}